@@ -0,0 +1,260 @@
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+
+use pyo3::buffer::{Element, PyBuffer};
+use pyo3::types::PyBytes;
+use pyo3::{PyObject, Python, ToPyObject};
+use serde::de::{self, Deserialize, DeserializeOwned, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use super::error::Error;
+
+/// Numeric types which can be copied in bulk from Python buffers into a `NumericArray`
+pub trait ArrayElement: Element + Copy + Serialize + DeserializeOwned {
+    /// Newtype struct name recognised by this crate's `Serializer` and `Deserializer`
+    #[doc(hidden)]
+    const TOKEN: &'static str;
+}
+
+macro_rules! array_elements {
+    ($($ty:ty => $typecode:expr),*) => {
+        $(impl ArrayElement for $ty {
+            const TOKEN: &'static str =
+                concat!("$serde_pyo3::private::NumericArray<", stringify!($ty), ">");
+        })*
+
+        /// The `array` module typecode for a `NumericArray` newtype name
+        pub(crate) fn typecode(name: &str) -> Option<&'static str> {
+            $(if name == <$ty as ArrayElement>::TOKEN {
+                return Some($typecode);
+            })*
+            None
+        }
+
+        /// Whether a buffer can be copied directly into the `NumericArray` with the given name
+        pub(crate) fn is_compatible(name: &str, buf: &PyBuffer) -> Option<bool> {
+            $(if name == <$ty as ArrayElement>::TOKEN {
+                return Some(
+                    buf.dimensions() == 1
+                        && buf.item_size() == mem::size_of::<$ty>()
+                        && <$ty as Element>::is_compatible_format(buf.format()),
+                );
+            })*
+            None
+        }
+    };
+}
+
+array_elements!(
+    i8 => "b", u8 => "B", i16 => "h", u16 => "H", i32 => "i", u32 => "I",
+    i64 => "q", u64 => "Q", f32 => "f", f64 => "d"
+);
+
+thread_local! {
+    /// Set by this crate's `Serializer` to receive the raw bytes of the next `NumericArray`
+    static RAW: Cell<bool> = Cell::new(false);
+    /// The raw bytes written by a `NumericArray` for this crate's `Serializer`
+    static RAW_BYTES: RefCell<Option<PyObject>> = RefCell::new(None);
+    /// A compatible buffer handed over by this crate's `Deserializer`
+    static BUFFER: RefCell<Option<PyBuffer>> = RefCell::new(None);
+}
+
+/// Ask the `NumericArray` being serialized to hand over its contents as a bytes object
+pub(crate) fn request_raw() {
+    RAW.with(|raw| raw.set(true));
+}
+
+/// Take the bytes object written by the last `NumericArray`, if any
+pub(crate) fn take_raw() -> Option<PyObject> {
+    RAW.with(|raw| raw.set(false));
+    RAW_BYTES.with(|bytes| bytes.borrow_mut().take())
+}
+
+/// Hand a buffer to the `NumericArray` being deserialized
+pub(crate) fn put_buffer(buf: PyBuffer) {
+    BUFFER.with(|buffer| *buffer.borrow_mut() = Some(buf));
+}
+
+/// A vector of numbers, copied in bulk from Python buffers such as `array.array` or numpy arrays
+/// rather than element by element, and serialized by `to_py` as an `array.array`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NumericArray<T>(pub Vec<T>);
+
+impl<T> NumericArray<T> {
+    pub fn new(values: Vec<T>) -> Self {
+        NumericArray(values)
+    }
+
+    pub fn into_inner(self) -> Vec<T> {
+        self.0
+    }
+}
+
+impl<T> From<Vec<T>> for NumericArray<T> {
+    fn from(values: Vec<T>) -> Self {
+        NumericArray(values)
+    }
+}
+
+impl<T> std::ops::Deref for NumericArray<T> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        &self.0
+    }
+}
+
+impl<T: ArrayElement> Serialize for NumericArray<T> {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(T::TOKEN, &Raw(&self.0))
+    }
+}
+
+/// The elements of an array, as raw bytes for this crate's `Serializer` or a sequence otherwise
+struct Raw<'a, T>(&'a [T]);
+
+impl<'a, T: ArrayElement> Serialize for Raw<'a, T> {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if RAW.with(|raw| raw.replace(false)) {
+            // the elements are plain numbers in native byte order, as `array.array` expects
+            let bytes = unsafe {
+                std::slice::from_raw_parts(self.0.as_ptr() as *const u8, mem::size_of_val(self.0))
+            };
+            let gil = Python::acquire_gil();
+            let py = gil.python();
+            let bytes = PyBytes::new(py, bytes).to_object(py);
+            RAW_BYTES.with(|raw| *raw.borrow_mut() = Some(bytes));
+            serializer.serialize_unit()
+        } else {
+            self.0.serialize(serializer)
+        }
+    }
+}
+
+struct NumericArrayVisitor<T>(PhantomData<T>);
+
+impl<'de, T: ArrayElement> Visitor<'de> for NumericArrayVisitor<T> {
+    type Value = NumericArray<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence of numbers")
+    }
+
+    // this crate's `Deserializer` hands over buffers which can be copied directly
+    fn visit_unit<E>(self) -> std::result::Result<NumericArray<T>, E>
+    where
+        E: de::Error,
+    {
+        let buf = BUFFER
+            .with(|buffer| buffer.borrow_mut().take())
+            .ok_or_else(|| E::custom("expected a buffer from serde_pyo3"))?;
+        let gil = Python::acquire_gil();
+        buf.to_vec::<T>(gil.python())
+            .map(NumericArray)
+            .map_err(|err| E::custom(Error::from(err)))
+    }
+
+    fn visit_newtype_struct<D>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<NumericArray<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Vec::deserialize(deserializer).map(NumericArray)
+    }
+}
+
+impl<'de, T: ArrayElement> Deserialize<'de> for NumericArray<T> {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_newtype_struct(T::TOKEN, NumericArrayVisitor(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_py, to_py};
+    use pyo3::py_run;
+    use pyo3::types::PyDict;
+    use pyo3::AsPyRef;
+
+    #[test]
+    fn test_from_buffer() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let locals = PyDict::new(py);
+        py.run(
+            r#"
+import array
+doubles = array.array('d', [1.0, 2.5, -3.0])
+ints = memoryview(array.array('i', [1, -2, 3]))
+longs = array.array('q', [2**40])
+floats = array.array('f', [0.5])
+grid = memoryview(bytes(4)).cast('B', [2, 2])
+"#,
+            None,
+            Some(locals),
+        )
+        .unwrap();
+        let get = |name: &str| locals.get_item(name).unwrap();
+
+        let result: NumericArray<f64> = from_py(py, get("doubles")).unwrap();
+        assert_eq!(result.0, vec![1.0, 2.5, -3.0]);
+        let result: NumericArray<i32> = from_py(py, get("ints")).unwrap();
+        assert_eq!(result.0, vec![1, -2, 3]);
+        let result: NumericArray<i64> = from_py(py, get("longs")).unwrap();
+        assert_eq!(result.0, vec![1 << 40]);
+
+        // other inputs are converted element by element
+        let result: NumericArray<f64> = from_py(py, get("floats")).unwrap();
+        assert_eq!(result.0, vec![0.5]);
+        assert!(from_py::<NumericArray<u8>>(py, get("grid")).is_err());
+        let input = py.eval("[1, 2]", None, None).unwrap();
+        let result: NumericArray<u16> = from_py(py, input).unwrap();
+        assert_eq!(result.0, vec![1, 2]);
+        let input = py.eval("['x']", None, None).unwrap();
+        assert!(from_py::<NumericArray<f32>>(py, input).is_err());
+    }
+
+    #[test]
+    fn test_to_array() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        let doubles = to_py(py, &NumericArray(vec![1.0f64, -2.5])).unwrap();
+        let bytes = to_py(py, &NumericArray(vec![0u8, 255])).unwrap();
+        py_run!(
+            py,
+            doubles bytes,
+            r#"
+            import array
+            assert isinstance(doubles, array.array)
+            assert doubles.typecode == 'd' and doubles.tolist() == [1.0, -2.5]
+            assert bytes.typecode == 'B' and bytes.tolist() == [0, 255]
+        "#
+        );
+
+        let samples = NumericArray(vec![1i64 << 40, -1]);
+        let output = to_py(py, &samples).unwrap();
+        let result: NumericArray<i64> = from_py(py, output.as_ref(py)).unwrap();
+        assert_eq!(result, samples);
+
+        let json = serde_json::to_string(&samples).unwrap();
+        assert_eq!(json, "[1099511627776,-1]");
+        assert_eq!(
+            serde_json::from_str::<NumericArray<i64>>(&json).unwrap(),
+            samples
+        );
+    }
+}
@@ -0,0 +1,548 @@
+use std::collections::HashMap;
+
+use pyo3::types::{PyByteArray, PyBytes, PyDict, PyIterator, PyList, PyString};
+use pyo3::{ObjectProtocol, PyAny, PyErr, PyObject, PyTryFrom, PyTypeInfo, Python, ToPyObject};
+use serde::de::{self, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{self, Impossible};
+use serde::{forward_to_deserialize_any, Deserialize, Serialize};
+
+use super::de::Deserializer;
+use super::error::{Error, Found, PathSegment, Result};
+use super::options::{DeserializerOptions, SerializerOptions};
+use super::ser::Serializer;
+
+/// Serialize a sequence of structs into a dict of lists, one list per field
+pub fn to_py_columnar<T>(py: Python, rows: &[T]) -> Result<PyObject>
+where
+    T: Serialize,
+{
+    to_py_columnar_with(py, rows, &SerializerOptions::default())
+}
+
+/// Serialize a sequence of structs into a dict of lists, using the given options for each value
+pub fn to_py_columnar_with<T>(
+    py: Python,
+    rows: &[T],
+    options: &SerializerOptions,
+) -> Result<PyObject>
+where
+    T: Serialize,
+{
+    let serializer = Serializer::new(py, options);
+    let mut columns = Columns {
+        index: HashMap::new(),
+        names: Vec::new(),
+        values: Vec::new(),
+    };
+    for (row, value) in rows.iter().enumerate() {
+        value.serialize(RowSerializer {
+            serializer: &serializer,
+            columns: &mut columns,
+            row,
+        })?;
+        // fields skipped in this row are filled with None
+        for column in columns.values.iter_mut() {
+            if column.len() == row {
+                column.push(py.None());
+            }
+        }
+    }
+    let dict = PyDict::new(py);
+    for (name, values) in columns.names.into_iter().zip(columns.values) {
+        dict.set_item(name, PyList::new(py, values))?;
+    }
+    Ok(dict.to_object(py))
+}
+
+/// Deserialize a dict of equal length lists into a sequence of structs
+pub fn from_py_columnar<'de, T>(py: Python<'de>, input: &'de PyAny) -> Result<T>
+where
+    T: Deserialize<'de>,
+{
+    from_py_columnar_with(py, input, &DeserializerOptions::default())
+}
+
+/// Deserialize a dict of equal length lists into a sequence of structs, using the given options
+/// for each value
+pub fn from_py_columnar_with<'de, T>(
+    py: Python<'de>,
+    input: &'de PyAny,
+    options: &DeserializerOptions,
+) -> Result<T>
+where
+    T: Deserialize<'de>,
+{
+    let dict = <PyDict as PyTryFrom>::try_from(input)
+        .map_err(|_| Error::ExpectedDict(Found::new(input)))?;
+    let mut columns = Vec::with_capacity(dict.len());
+    for (key, value) in dict.iter() {
+        let name = key
+            .extract::<String>()
+            .map_err(|_| Error::ExpectedString(Found::new(key)))?;
+        // a str or bytes column would otherwise be split into characters
+        let is_text = <PyString as PyTypeInfo>::is_instance(value)
+            || <PyBytes as PyTypeInfo>::is_instance(value)
+            || <PyByteArray as PyTypeInfo>::is_instance(value);
+        let iter = if is_text {
+            None
+        } else {
+            PyIterator::from_object(py, value).ok()
+        };
+        let values = iter
+            .ok_or_else(|| {
+                Error::ExpectedList(Found::new(value)).at(PathSegment::Key(name.clone()))
+            })?
+            .collect::<std::result::Result<Vec<_>, PyErr>>()?;
+        columns.push((name, values));
+    }
+    if let Some((first, rows)) = columns.first().map(|(name, values)| (name, values.len())) {
+        for (name, values) in &columns {
+            if values.len() != rows {
+                return Err(Error::Message(format!(
+                    "column '{}' has {} values, but column '{}' has {}",
+                    name,
+                    values.len(),
+                    first,
+                    rows
+                )));
+            }
+        }
+    }
+    let root = Deserializer::from_py(py, input).with_options(*options);
+    let result = T::deserialize(ColumnsDeserializer {
+        root: &root,
+        columns: &columns,
+    });
+    root.finish(result)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+struct Columns {
+    index: HashMap<&'static str, usize>,
+    names: Vec<&'static str>,
+    values: Vec<Vec<PyObject>>,
+}
+
+/// Serializes one row, appending each field of a struct to its column
+struct RowSerializer<'a> {
+    serializer: &'a Serializer<'a>,
+    columns: &'a mut Columns,
+    row: usize,
+}
+
+fn not_a_struct() -> Error {
+    Error::Serialization("columnar rows must be structs".to_owned())
+}
+
+macro_rules! not_a_struct {
+    ($($method:ident($($arg:ty),*);)*) => {
+        $(fn $method(self, $(_: $arg),*) -> Result<()> {
+            Err(not_a_struct())
+        })*
+    };
+}
+
+impl<'a> ser::Serializer for RowSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    not_a_struct! {
+        serialize_bool(bool);
+        serialize_i8(i8);
+        serialize_i16(i16);
+        serialize_i32(i32);
+        serialize_i64(i64);
+        serialize_i128(i128);
+        serialize_u8(u8);
+        serialize_u16(u16);
+        serialize_u32(u32);
+        serialize_u64(u64);
+        serialize_u128(u128);
+        serialize_f32(f32);
+        serialize_f64(f64);
+        serialize_char(char);
+        serialize_str(&str);
+        serialize_bytes(&[u8]);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(&'static str);
+        serialize_unit_variant(&'static str, u32, &'static str);
+    }
+
+    fn serialize_some<T>(self, _value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(not_a_struct())
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(not_a_struct())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(not_a_struct())
+    }
+}
+
+impl<'a> ser::SerializeStruct for RowSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let py = self.serializer.py;
+        let cell = value.serialize(self.serializer)?;
+        let columns = &mut *self.columns;
+        let row = self.row;
+        let idx = match columns.index.get(key) {
+            Some(&idx) => idx,
+            None => {
+                columns.names.push(key);
+                columns.values.push((0..row).map(|_| py.None()).collect());
+                columns.index.insert(key, columns.names.len() - 1);
+                columns.names.len() - 1
+            }
+        };
+        let column = &mut columns.values[idx];
+        if column.len() > row {
+            return Err(Error::Serialization(format!(
+                "field '{}' repeated in row {}",
+                key, row
+            )));
+        }
+        column.push(cell);
+        Ok(())
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+type Column<'de> = (String, Vec<&'de PyAny>);
+
+/// Presents a dict of columns as a sequence of rows
+struct ColumnsDeserializer<'a, 'de> {
+    root: &'a Deserializer<'de>,
+    columns: &'a [Column<'de>],
+}
+
+impl<'a, 'de> de::Deserializer<'de> for ColumnsDeserializer<'a, 'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let len = self.columns.first().map_or(0, |(_, values)| values.len());
+        visitor.visit_seq(Rows {
+            root: self.root,
+            columns: self.columns,
+            row: 0,
+            len,
+        })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct Rows<'a, 'de> {
+    root: &'a Deserializer<'de>,
+    columns: &'a [Column<'de>],
+    row: usize,
+    len: usize,
+}
+
+impl<'a, 'de> SeqAccess<'de> for Rows<'a, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if self.row == self.len {
+            return Ok(None);
+        }
+        let row = RowDeserializer {
+            root: self.root,
+            columns: self.columns,
+            row: self.row,
+        };
+        self.row += 1;
+        seed.deserialize(row).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len - self.row)
+    }
+}
+
+/// Presents one row of the columns as a map from field name to value
+struct RowDeserializer<'a, 'de> {
+    root: &'a Deserializer<'de>,
+    columns: &'a [Column<'de>],
+    row: usize,
+}
+
+impl<'a, 'de> de::Deserializer<'de> for RowDeserializer<'a, 'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_map(Cells {
+            row: self,
+            column: 0,
+        })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct Cells<'a, 'de> {
+    row: RowDeserializer<'a, 'de>,
+    column: usize,
+}
+
+impl<'a, 'de> MapAccess<'de> for Cells<'a, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.row.columns.get(self.column) {
+            Some((name, _)) => seed
+                .deserialize(name.as_str().into_deserializer())
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let columns = self.row.columns;
+        let (name, values) = &columns[self.column];
+        self.column += 1;
+        let row = self.row.row;
+        let cell = values[row];
+        self.row.root.nested(
+            cell,
+            || PathSegment::Index(row),
+            |de| {
+                de.nested(
+                    cell,
+                    || PathSegment::Key(name.clone()),
+                    |de| seed.deserialize(de),
+                )
+            },
+        )
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.row.columns.len() - self.column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::py_run;
+    use pyo3::types::IntoPyDict;
+    use pyo3::AsPyRef;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Row {
+        name: String,
+        score: f64,
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        tag: Option<String>,
+    }
+
+    fn rows() -> Vec<Row> {
+        vec![
+            Row {
+                name: "a".to_owned(),
+                score: 1.5,
+                tag: None,
+            },
+            Row {
+                name: "b".to_owned(),
+                score: -2.0,
+                tag: Some("x".to_owned()),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_to_py_columnar() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        let output = to_py_columnar(py, &rows()).unwrap();
+        py_run!(
+            py,
+            output,
+            r#"assert output == {"name": ["a", "b"], "score": [1.5, -2.0], "tag": [None, "x"]}"#
+        );
+
+        let output = to_py_columnar::<Row>(py, &[]).unwrap();
+        py_run!(py, output, "assert output == {}");
+
+        assert!(to_py_columnar(py, &[1, 2]).is_err());
+    }
+
+    #[test]
+    fn test_from_py_columnar() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        let input = py
+            .eval(
+                r#"{"name": ["a", "b"], "score": (1.5, -2), "tag": [None, "x"]}"#,
+                None,
+                None,
+            )
+            .unwrap();
+        let result: Vec<Row> = from_py_columnar(py, input).unwrap();
+        assert_eq!(result, rows());
+
+        let output = to_py_columnar(py, &rows()).unwrap();
+        let result: Vec<Row> = from_py_columnar(py, output.as_ref(py)).unwrap();
+        assert_eq!(result, rows());
+
+        let input = py
+            .eval(r#"{"name": ["a", "b"], "score": [1.5]}"#, None, None)
+            .unwrap();
+        let err = from_py_columnar::<Vec<Row>>(py, input).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "column 'score' has 1 values, but column 'name' has 2"
+        );
+
+        let input = vec![("name", vec!["a", "b"]), ("score", vec!["1", "x"])].into_py_dict(py);
+        let err = from_py_columnar::<Vec<Row>>(py, input.as_ref()).unwrap_err();
+        assert_eq!(err.path().unwrap().to_string(), "[0].score");
+
+        let input = py
+            .eval(r#"{"name": ["a"], "score": 1.5}"#, None, None)
+            .unwrap();
+        let err = from_py_columnar::<Vec<Row>>(py, input).unwrap_err();
+        assert_eq!(err.expected(), Some("list"));
+        assert_eq!(err.path().unwrap().to_string(), ".score");
+
+        let input = py
+            .eval(r#"{"name": "ab", "score": [1, 2]}"#, None, None)
+            .unwrap();
+        let err = from_py_columnar::<Vec<Row>>(py, input).unwrap_err();
+        assert_eq!(err.expected(), Some("list"));
+        assert_eq!(err.path().unwrap().to_string(), ".name");
+    }
+
+    #[test]
+    fn test_from_py_columnar_with() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        let input = py
+            .eval(
+                r#"{"name": ["a", 2, 3], "score": ["x", 1, 2.5]}"#,
+                None,
+                None,
+            )
+            .unwrap();
+        let options = DeserializerOptions::new().collect_errors(true);
+        let err = from_py_columnar_with::<Vec<Row>>(py, input, &options).unwrap_err();
+        match err {
+            Error::Multiple(errors) => {
+                let paths: Vec<String> = errors
+                    .iter()
+                    .map(|err| err.path().unwrap().to_string())
+                    .collect();
+                assert_eq!(paths, vec!["[0].score", "[1].name", "[2].name"]);
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+}
@@ -1,6 +1,17 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
 use pyo3::buffer::PyBuffer;
-use pyo3::types::{PyDict, PyIterator, PyList, PySequence, PyString, PyTuple};
-use pyo3::{AsPyPointer, FromPyObject, PyAny, PyTryFrom, PyTypeInfo, Python};
+use pyo3::exceptions::AttributeError;
+use pyo3::types::{
+    PyBool, PyByteArray, PyBytes, PyDict, PyFloat, PyFrozenSet, PyIterator, PyList, PyLong,
+    PySequence, PySet, PyString, PyTuple,
+};
+use pyo3::{
+    AsPyPointer, FromPyObject, ObjectProtocol, PyAny, PyErr, PyObject, PyTryFrom, PyTypeInfo,
+    Python,
+};
 
 use serde::de::{
     self, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess,
@@ -8,16 +19,100 @@ use serde::de::{
 };
 use serde::Deserialize;
 
-use super::error::{Error, Result};
+use super::array;
+use super::error::{Error, Found, PathSegment, Result};
+use super::options::{Coercion, DeserializerOptions, EnumRepr};
+use super::placeholder::Placeholder;
+use super::pyany;
+
+/// Fail with a type mismatch, or record it and visit a placeholder value when collecting errors
+macro_rules! mismatch {
+    ($de:expr, $err:expr, $visitor:ident, $method:ident $(, $arg:expr)*) => {
+        $de.recover($err, $visitor, |placeholder, visitor| {
+            de::Deserializer::$method(placeholder, $($arg,)* visitor)
+        })
+    };
+}
+
+/// Unwrap a result, returning early on a type mismatch as per `mismatch!`
+macro_rules! tri {
+    ($de:expr, $result:expr, $visitor:ident, $method:ident $(, $arg:expr)*) => {
+        match $result {
+            Ok(val) => val,
+            Err(err) => return mismatch!($de, err, $visitor, $method $(, $arg)*),
+        }
+    };
+}
+
+/// Rust integer types which report values out of their range
+trait Integer: for<'a> FromPyObject<'a> + std::str::FromStr + Sized {
+    const SIGNED: bool;
+
+    fn overflow(value: String) -> Error {
+        Error::IntegerOverflow {
+            bits: (std::mem::size_of::<Self>() * 8) as u32,
+            signed: Self::SIGNED,
+            value,
+        }
+    }
+}
+
+macro_rules! impl_integer {
+    ($($ty:ty: $signed:expr),*) => {
+        $(impl Integer for $ty {
+            const SIGNED: bool = $signed;
+        })*
+    };
+}
+
+impl_integer!(i8: true, i16: true, i32: true, i64: true, i128: true);
+impl_integer!(u8: false, u16: false, u32: false, u64: false, u128: false);
 
+#[derive(Clone)]
 pub struct Deserializer<'de> {
     py: Python<'de>,
     input: &'de PyAny,
+    options: DeserializerOptions,
+    errors: Option<Rc<RefCell<Vec<Error>>>>,
+    substituted: Cell<bool>,
 }
 
 impl<'de> Deserializer<'de> {
     pub fn from_py(py: Python<'de>, input: &'de PyAny) -> Self {
-        Deserializer { py, input }
+        Deserializer {
+            py,
+            input,
+            options: DeserializerOptions::default(),
+            errors: None,
+            substituted: Cell::new(false),
+        }
+    }
+
+    pub fn with_options(mut self, options: DeserializerOptions) -> Self {
+        self.options = options;
+        if options.collect_errors && self.errors.is_none() {
+            self.errors = Some(Rc::new(RefCell::new(Vec::new())));
+        }
+        self
+    }
+
+    /// Combine a deserialization result with any errors collected along the way
+    pub fn finish<T>(&self, result: Result<T>) -> Result<T> {
+        let mut errors = match &self.errors {
+            Some(errors) => errors.replace(Vec::new()),
+            None => return result,
+        };
+        match self.suppress(result) {
+            Ok(value) if errors.is_empty() => Ok(value),
+            Ok(_) => Err(Error::Multiple(errors)),
+            Err(Error::Multiple(ref suppressed)) if suppressed.is_empty() => {
+                Err(Error::Multiple(errors))
+            }
+            Err(err) => {
+                errors.push(err);
+                Err(Error::Multiple(errors))
+            }
+        }
     }
 }
 
@@ -25,21 +120,197 @@ pub fn from_py<'de, T>(py: Python<'de>, input: &'de PyAny) -> Result<T>
 where
     T: Deserialize<'de>,
 {
-    let mut deserializer = Deserializer::from_py(py, input);
-    Ok(T::deserialize(&mut deserializer)?)
+    from_py_with(py, input, &DeserializerOptions::default())
+}
+
+pub fn from_py_with<'de, T>(
+    py: Python<'de>,
+    input: &'de PyAny,
+    options: &DeserializerOptions,
+) -> Result<T>
+where
+    T: Deserialize<'de>,
+{
+    let mut deserializer = Deserializer::from_py(py, input).with_options(*options);
+    let result = T::deserialize(&mut deserializer);
+    deserializer.finish(result)
+}
+
+/// Deserialize while collecting every type mismatch into an `Error::Multiple`
+pub fn from_py_collect<'de, T>(py: Python<'de>, input: &'de PyAny) -> Result<T>
+where
+    T: Deserialize<'de>,
+{
+    from_py_with(py, input, &DeserializerOptions::new().collect_errors(true))
 }
 
 impl<'de> Deserializer<'de> {
     #[inline]
-    fn expect<T>(&mut self, expected: Error) -> Result<T>
+    fn expect<T>(&mut self, expected: fn(Found) -> Error) -> Result<T>
     where
         T: for<'a> FromPyObject<'a>,
     {
         if let Ok(result) = T::extract(self.input) {
             Ok(result)
         } else {
-            Err(expected)
+            Err(expected(self.found()))
+        }
+    }
+
+    fn found(&self) -> Found {
+        Found::new(self.input)
+    }
+
+    fn expect_integer<T>(&mut self) -> Result<T>
+    where
+        T: Integer,
+    {
+        match self.options.coercion {
+            Coercion::Strict if <PyBool as PyTypeInfo>::is_instance(self.input) => {
+                return Err(Error::ExpectedInteger(self.found()))
+            }
+            Coercion::Lenient => {
+                if let Some(strval) = self.try_from::<PyString>() {
+                    let strval = strval.to_string()?;
+                    let strval = strval.trim();
+                    return strval.parse().map_err(|_| {
+                        let digits = strval.trim_start_matches(|c| c == '-' || c == '+');
+                        if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) {
+                            T::overflow(strval.to_owned())
+                        } else {
+                            Error::ExpectedInteger(self.found())
+                        }
+                    });
+                }
+            }
+            _ => (),
+        }
+        if let Ok(result) = T::extract(self.input) {
+            Ok(result)
+        } else if <PyLong as PyTypeInfo>::is_instance(self.input) {
+            Err(T::overflow(
+                self.input.str()?.to_string_lossy().into_owned(),
+            ))
+        } else {
+            Err(Error::ExpectedInteger(self.found()))
+        }
+    }
+
+    fn expect_float<T>(&mut self) -> Result<T>
+    where
+        T: for<'a> FromPyObject<'a> + std::str::FromStr,
+    {
+        match self.options.coercion {
+            Coercion::Strict if !<PyFloat as PyTypeInfo>::is_instance(self.input) => {
+                return Err(Error::ExpectedFloat(self.found()))
+            }
+            Coercion::Lenient => {
+                if let Some(strval) = self.try_from::<PyString>() {
+                    return strval
+                        .to_string()?
+                        .trim()
+                        .parse()
+                        .map_err(|_| Error::ExpectedFloat(self.found()));
+                }
+            }
+            _ => (),
         }
+        self.expect(Error::ExpectedFloat)
+    }
+
+    /// The decimal string of an int too large for 128 bits, when enabled
+    fn big_int(&self) -> Result<Option<String>> {
+        if self.options.big_int_strings
+            && <PyLong as PyTypeInfo>::is_instance(self.input)
+            && i128::extract(self.input).is_err()
+            && u128::extract(self.input).is_err()
+        {
+            Ok(Some(self.input.str()?.to_string()?.into_owned()))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn seq_access(&mut self) -> Result<SeqIter<'de>> {
+        if self.options.coercion == Coercion::Strict
+            && (<PyString as PyTypeInfo>::is_instance(self.input)
+                || <PyBytes as PyTypeInfo>::is_instance(self.input)
+                || <PyByteArray as PyTypeInfo>::is_instance(self.input))
+        {
+            return Err(Error::ExpectedList(self.found()));
+        }
+        let size = self.length_hint()?;
+        match PyIterator::from_object(self.py, self.input) {
+            Ok(iter) => Ok(SeqIter::new(self, iter, size)),
+            Err(_) => Err(Error::ExpectedList(self.found())),
+        }
+    }
+
+    /// The expected number of items, from `__len__` or `__length_hint__`
+    fn length_hint(&self) -> Result<Option<usize>> {
+        let hint = unsafe { pyo3::ffi::PyObject_LengthHint(self.input.as_ptr(), -1) };
+        if hint < 0 && PyErr::occurred(self.py) {
+            // a failing `__length_hint__` only means the size is unknown
+            PyErr::fetch(self.py);
+        }
+        Ok(if hint < 0 { None } else { Some(hint as usize) })
+    }
+
+    /// Whether the input has a callable attribute with the given name
+    fn has_method(&self, name: &str) -> bool {
+        self.input
+            .getattr(name)
+            .map_or(false, |attr| attr.is_callable())
+    }
+
+    fn mapping_access(&mut self) -> Result<MappingIter<'de>> {
+        let items = self.has_method("items");
+        let iter = if items {
+            self.input.call_method0("items")?
+        } else if self.has_method("keys") && self.input.hasattr("__getitem__")? {
+            self.input.call_method0("keys")?
+        } else {
+            return Err(Error::ExpectedDict(self.found()));
+        };
+        let size = self.length_hint()?;
+        match PyIterator::from_object(self.py, iter) {
+            Ok(iter) => Ok(MappingIter::new(self, iter, items, size)),
+            Err(_) => Err(Error::ExpectedDict(self.found())),
+        }
+    }
+
+    /// Whether the input is a namedtuple instance, which is read by field name
+    fn is_namedtuple(&self) -> Result<bool> {
+        Ok(<PyTuple as PyTypeInfo>::is_instance(self.input)
+            && self.input.hasattr("_fields")?
+            && self.input.hasattr("_asdict")?)
+    }
+
+    /// Whether the input is a sequence which may hold struct fields by position
+    fn is_positional(&self) -> bool {
+        <PySequence as PyTryFrom>::try_from(self.input).is_ok()
+            && !<PyString as PyTypeInfo>::is_instance(self.input)
+            && !<PyBytes as PyTypeInfo>::is_instance(self.input)
+            && !<PyByteArray as PyTypeInfo>::is_instance(self.input)
+    }
+
+    /// Whether the input is an object with attributes which may hold struct fields
+    fn has_attributes(&self) -> Result<bool> {
+        Ok(self.input.hasattr("__dict__")? || self.input.hasattr("__slots__")?)
+    }
+
+    /// The input as a dict, copying it when given another kind of mapping
+    fn as_dict(&mut self) -> Result<Option<&'de PyDict>> {
+        if let Some(dict) = self.try_from::<PyDict>() {
+            return Ok(Some(dict));
+        }
+        if !is_abc_instance(self.py, self.input, "Mapping")? {
+            return Ok(None);
+        }
+        let dict = self.py.import("builtins")?.call1("dict", (self.input,))?;
+        Ok(Some(
+            <PyDict as PyTryFrom>::try_from(dict).map_err(PyErr::from)?,
+        ))
     }
 
     #[inline]
@@ -62,6 +333,64 @@ impl<'de> Deserializer<'de> {
     fn is_none(&self) -> bool {
         self.input.as_ptr() == unsafe { pyo3::ffi::Py_None() }
     }
+
+    /// Create a deserializer for a value nested inside the current input
+    fn child<'b>(&self, input: &'b PyAny) -> Deserializer<'b>
+    where
+        'de: 'b,
+    {
+        Deserializer {
+            py: self.py,
+            input,
+            options: self.options,
+            errors: self.errors.clone(),
+            substituted: Cell::new(false),
+        }
+    }
+
+    /// Deserialize a nested value, attributing any errors to the given path segment
+    pub(crate) fn nested<'b, R, S, F>(&self, input: &'b PyAny, segment: S, f: F) -> Result<R>
+    where
+        'de: 'b,
+        S: Fn() -> PathSegment,
+        F: FnOnce(&mut Deserializer<'b>) -> Result<R>,
+    {
+        let mark = self.errors.as_ref().map(|errors| errors.borrow().len());
+        let mut child = self.child(input);
+        let result = f(&mut child);
+        let result = child.suppress(result);
+        if let (Some(errors), Some(mark)) = (&self.errors, mark) {
+            let mut errors = errors.borrow_mut();
+            let located: Vec<Error> = errors.drain(mark..).map(|err| err.at(segment())).collect();
+            errors.extend(located);
+        }
+        result.map_err(|err| err.at(segment()))
+    }
+
+    /// Replace an error caused by a placeholder value with an empty `Error::Multiple`, as it adds
+    /// nothing to the error already recorded
+    fn suppress<R>(&self, result: Result<R>) -> Result<R> {
+        match result {
+            Err(_) if self.substituted.get() => Err(Error::Multiple(Vec::new())),
+            result => result,
+        }
+    }
+
+    /// Fail with a type mismatch, or record it and visit a placeholder value instead
+    fn recover<V, F>(&self, err: Error, visitor: V, fallback: F) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+        F: FnOnce(Placeholder, V) -> Result<V::Value>,
+    {
+        match &self.errors {
+            Some(errors) if err.is_recoverable() => {
+                errors.borrow_mut().push(err);
+                self.substituted.set(true);
+                fallback(Placeholder, visitor)
+            }
+            _ => Err(err),
+        }
+    }
 }
 
 impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
@@ -78,18 +407,44 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
             visitor.visit_borrowed_str(strval)
         } else if let Some(val) = self.try_extract::<bool>() {
             visitor.visit_bool(val)
-        } else if let Some(val) = self.try_extract::<u64>() {
-            visitor.visit_u64(val)
-        } else if let Some(val) = self.try_extract::<f64>() {
-            visitor.visit_f64(val)
-        } else if <PyList as PyTypeInfo>::is_instance(self.input)
-            || <PyTuple as PyTypeInfo>::is_instance(self.input)
+        } else if <PyLong as PyTypeInfo>::is_instance(self.input) {
+            if let Some(val) = self.try_extract::<i64>() {
+                visitor.visit_i64(val)
+            } else if let Some(val) = self.try_extract::<u64>() {
+                visitor.visit_u64(val)
+            } else if let Some(val) = self.try_extract::<i128>() {
+                visitor.visit_i128(val)
+            } else if let Some(val) = self.try_extract::<u128>() {
+                visitor.visit_u128(val)
+            } else if let Some(val) = self.big_int()? {
+                visitor.visit_string(val)
+            } else {
+                let err = u128::overflow(self.input.str()?.to_string_lossy().into_owned());
+                mismatch!(self, err, visitor, deserialize_any)
+            }
+        } else if let Some(val) = self.try_from::<PyFloat>() {
+            visitor.visit_f64(val.value())
+        } else if <PyBytes as PyTypeInfo>::is_instance(self.input)
+            || <PyByteArray as PyTypeInfo>::is_instance(self.input)
+        {
+            self.deserialize_bytes(visitor)
+        } else if let Some(dict) = self.try_from::<PyDict>() {
+            visitor.visit_map(DictIter::new(self, dict))
+        } else if is_abc_instance(self.py, self.input, "Mapping")? {
+            let access = self.mapping_access()?;
+            visitor.visit_map(access)
+        } else if <PySet as PyTypeInfo>::is_instance(self.input)
+            || <PyFrozenSet as PyTypeInfo>::is_instance(self.input)
+            || <PySequence as PyTryFrom>::try_from(self.input).is_ok()
         {
             self.deserialize_seq(visitor)
-        } else if <PyDict as PyTypeInfo>::is_instance(self.input) {
-            self.deserialize_map(visitor)
         } else {
-            Err(Error::Unsupported)
+            mismatch!(
+                self,
+                Error::Unsupported(self.found()),
+                visitor,
+                deserialize_any
+            )
         }
     }
 
@@ -97,88 +452,130 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        visitor.visit_bool(self.expect(Error::ExpectedBoolean)?)
+        let val = tri!(
+            self,
+            self.expect(Error::ExpectedBoolean),
+            visitor,
+            deserialize_bool
+        );
+        visitor.visit_bool(val)
     }
 
     fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_i8(self.expect(Error::ExpectedInteger)?)
+        let val = tri!(self, self.expect_integer(), visitor, deserialize_i8);
+        visitor.visit_i8(val)
     }
 
     fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_i16(self.expect(Error::ExpectedInteger)?)
+        let val = tri!(self, self.expect_integer(), visitor, deserialize_i16);
+        visitor.visit_i16(val)
     }
 
     fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_i32(self.expect(Error::ExpectedInteger)?)
+        let val = tri!(self, self.expect_integer(), visitor, deserialize_i32);
+        visitor.visit_i32(val)
     }
 
     fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_i64(self.expect(Error::ExpectedInteger)?)
+        let val = tri!(self, self.expect_integer(), visitor, deserialize_i64);
+        visitor.visit_i64(val)
+    }
+
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let val = tri!(self, self.expect_integer(), visitor, deserialize_i128);
+        visitor.visit_i128(val)
     }
 
     fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_u8(self.expect(Error::ExpectedInteger)?)
+        let val = tri!(self, self.expect_integer(), visitor, deserialize_u8);
+        visitor.visit_u8(val)
     }
 
     fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_u16(self.expect(Error::ExpectedInteger)?)
+        let val = tri!(self, self.expect_integer(), visitor, deserialize_u16);
+        visitor.visit_u16(val)
     }
 
     fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_u32(self.expect(Error::ExpectedInteger)?)
+        let val = tri!(self, self.expect_integer(), visitor, deserialize_u32);
+        visitor.visit_u32(val)
     }
 
     fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_u64(self.expect(Error::ExpectedInteger)?)
+        let val = tri!(self, self.expect_integer(), visitor, deserialize_u64);
+        visitor.visit_u64(val)
+    }
+
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let val = tri!(self, self.expect_integer(), visitor, deserialize_u128);
+        visitor.visit_u128(val)
     }
 
     fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_f32(self.expect(Error::ExpectedFloat)?)
+        let val = tri!(self, self.expect_float(), visitor, deserialize_f32);
+        visitor.visit_f32(val)
     }
 
     fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_f64(self.expect(Error::ExpectedFloat)?)
+        let val = tri!(self, self.expect_float(), visitor, deserialize_f64);
+        visitor.visit_f64(val)
     }
 
     fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        let strval = self.expect::<String>(Error::ExpectedChar)?;
-        if strval.len() == 1 {
-            visitor.visit_char(strval.chars().next().unwrap())
-        } else {
-            Err(Error::ExpectedChar)
+        let strval = tri!(
+            self,
+            self.expect::<String>(Error::ExpectedChar),
+            visitor,
+            deserialize_char
+        );
+        let mut chars = strval.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => mismatch!(
+                self,
+                Error::ExpectedChar(self.found()),
+                visitor,
+                deserialize_char
+            ),
         }
     }
 
@@ -189,8 +586,15 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         if let Some(strval) = self.try_from::<PyString>() {
             let strval = unsafe { std::str::from_utf8_unchecked(strval.as_bytes()?) };
             visitor.visit_borrowed_str(strval)
+        } else if let Some(val) = self.big_int()? {
+            visitor.visit_string(val)
         } else {
-            Err(Error::ExpectedString)
+            mismatch!(
+                self,
+                Error::ExpectedString(self.found()),
+                visitor,
+                deserialize_str
+            )
         }
     }
 
@@ -198,20 +602,39 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        visitor.visit_string(self.expect::<String>(Error::ExpectedString)?)
+        if let Some(val) = self.big_int()? {
+            return visitor.visit_string(val);
+        }
+        let val = tri!(
+            self,
+            self.expect::<String>(Error::ExpectedString),
+            visitor,
+            deserialize_string
+        );
+        visitor.visit_string(val)
     }
 
     fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        let buf = PyBuffer::get(self.py, self.input)?;
-        if let Some(_) = buf.as_slice::<u8>(self.py) {
+        let buf = tri!(
+            self,
+            PyBuffer::get(self.py, self.input).map_err(|_| Error::ExpectedBytes(self.found())),
+            visitor,
+            deserialize_bytes
+        );
+        if buf.as_slice::<u8>(self.py).is_some() {
             let buf: &[u8] =
                 unsafe { std::slice::from_raw_parts(buf.buf_ptr() as *const u8, buf.item_count()) };
             visitor.visit_borrowed_bytes(buf)
         } else {
-            Err(Error::ExpectedBytes)
+            mismatch!(
+                self,
+                Error::ExpectedBytes(self.found()),
+                visitor,
+                deserialize_bytes
+            )
         }
     }
 
@@ -219,7 +642,12 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        let bytes = self.expect::<Vec<u8>>(Error::ExpectedBytes)?;
+        let bytes = tri!(
+            self,
+            self.expect::<Vec<u8>>(Error::ExpectedBytes),
+            visitor,
+            deserialize_byte_buf
+        );
         visitor.visit_byte_buf(bytes)
     }
 
@@ -241,7 +669,12 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         if self.is_none() {
             visitor.visit_unit()
         } else {
-            Err(Error::ExpectedNone)
+            mismatch!(
+                self,
+                Error::ExpectedNone(self.found()),
+                visitor,
+                deserialize_unit
+            )
         }
     }
 
@@ -252,10 +685,22 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         self.deserialize_unit(visitor)
     }
 
-    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
+        if name == pyany::TOKEN {
+            pyany::put(PyObject::from(self.input));
+            return visitor.visit_unit();
+        }
+        if array::typecode(name).is_some() {
+            if let Ok(buf) = PyBuffer::get(self.py, self.input) {
+                if array::is_compatible(name, &buf) == Some(true) {
+                    array::put_buffer(buf);
+                    return visitor.visit_unit();
+                }
+            }
+        }
         visitor.visit_newtype_struct(self)
     }
 
@@ -263,37 +708,36 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        if let Some(seq) = self.try_from::<PySequence>() {
-            match PyIterator::from_object(self.py, seq) {
-                Ok(iter) => {
-                    let size = seq.len().map(|x| x as usize).ok();
-                    let value = visitor.visit_seq(SeqIter::new(self.py, iter, size))?;
-                    Ok(value)
-                }
-                Err(_) => Err(Error::ExpectedList),
-            }
-        } else {
-            Err(Error::ExpectedList)
-        }
+        let access = tri!(self, self.seq_access(), visitor, deserialize_seq);
+        visitor.visit_seq(access)
     }
 
-    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        let access = tri!(self, self.seq_access(), visitor, deserialize_tuple, len);
+        visitor.visit_seq(access)
     }
 
     fn deserialize_tuple_struct<V>(
         self,
-        _name: &'static str,
-        _len: usize,
+        name: &'static str,
+        len: usize,
         visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        let access = tri!(
+            self,
+            self.seq_access(),
+            visitor,
+            deserialize_tuple_struct,
+            name,
+            len
+        );
+        visitor.visit_seq(access)
     }
 
     fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
@@ -301,28 +745,55 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         V: Visitor<'de>,
     {
         if let Some(dict) = self.try_from::<PyDict>() {
-            visitor.visit_map(DictIter::new(self.py, dict))
-        } else {
-            Err(Error::ExpectedDict)
+            return visitor.visit_map(DictIter::new(self, dict));
         }
+        let access = tri!(self, self.mapping_access(), visitor, deserialize_map);
+        visitor.visit_map(access)
     }
 
     fn deserialize_struct<V>(
         self,
-        _name: &'static str,
-        _fields: &'static [&'static str],
+        name: &'static str,
+        fields: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_map(visitor)
+        if self.options.prefer_attributes && self.has_attributes()? {
+            return visitor.visit_map(AttrIter::new(self, fields));
+        }
+        if let Some(dict) = self.try_from::<PyDict>() {
+            return visitor.visit_map(DictIter::new(self, dict));
+        }
+        if self.is_namedtuple()? {
+            let dict = self.input.call_method0("_asdict")?;
+            let dict = <PyDict as PyTryFrom>::try_from(dict).map_err(PyErr::from)?;
+            return visitor.visit_map(DictIter::new(self, dict));
+        }
+        match self.mapping_access() {
+            Ok(access) => visitor.visit_map(access),
+            Err(Error::ExpectedDict(_)) if self.is_positional() => {
+                let len = self.input.len()?;
+                if len != fields.len() {
+                    let expected = format!("struct {} with {} elements", name, fields.len());
+                    let err = <Error as de::Error>::invalid_length(len, &expected.as_str());
+                    return mismatch!(self, err, visitor, deserialize_struct, name, fields);
+                }
+                let access = self.seq_access()?;
+                visitor.visit_seq(access)
+            }
+            Err(Error::ExpectedDict(_)) if self.has_attributes()? => {
+                visitor.visit_map(AttrIter::new(self, fields))
+            }
+            Err(err) => mismatch!(self, err, visitor, deserialize_struct, name, fields),
+        }
     }
 
     fn deserialize_enum<V>(
         self,
-        _name: &'static str,
-        _variants: &'static [&'static str],
+        name: &'static str,
+        variants: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value>
     where
@@ -330,21 +801,82 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     {
         if <PyString as PyTypeInfo>::is_instance(self.input) {
             let key: String = self.expect(Error::ExpectedString)?;
-            visitor.visit_enum(key.into_deserializer())
+            return visitor.visit_enum(key.into_deserializer());
+        }
+        if is_instance_of(self.py, self.input, "enum", "Enum")? {
+            // members whose value isn't a str, such as `IntEnum` members, are matched by name
+            let value = self.input.getattr("value")?;
+            let key = match value.extract::<String>() {
+                Ok(value) if self.options.match_enum_value => Ok(value),
+                _ => self.input.getattr("name")?.extract::<String>(),
+            };
+            if let Ok(key) = key {
+                return visitor.visit_enum(key.into_deserializer());
+            }
+        }
+        let dict = if let Some(dict) = self.as_dict()? {
+            dict
+        } else if self.options.enum_repr == EnumRepr::Untagged {
+            let err = Error::Unsupported(self.found());
+            return mismatch!(self, err, visitor, deserialize_enum, name, variants);
         } else {
-            if let Some(dict) = self.try_from::<PyDict>() {
+            let err = Error::ExpectedDict(self.found());
+            return mismatch!(self, err, visitor, deserialize_enum, name, variants);
+        };
+        match self.options.enum_repr {
+            EnumRepr::External => {
                 if let Some(key) = dict.keys().iter().next() {
                     if let Some(val) = dict.get_item(key) {
-                        let value = visitor.visit_enum(Enum::new(self.py, key, val))?;
+                        let value = visitor.visit_enum(Enum::new(self, key, Some(val)))?;
                         Ok(value)
                     } else {
                         Err(Error::ExpectedEnumValue)
                     }
                 } else {
-                    Err(Error::ExpectedEnumKey)
+                    mismatch!(
+                        self,
+                        Error::ExpectedEnumKey,
+                        visitor,
+                        deserialize_enum,
+                        name,
+                        variants
+                    )
+                }
+            }
+            EnumRepr::Internal { tag } => {
+                if let Some(key) = dict.get_item(tag) {
+                    let rest = dict.copy()?;
+                    rest.del_item(tag)?;
+                    visitor.visit_enum(Enum::new(self, key, Some(rest.as_ref())))
+                } else {
+                    mismatch!(
+                        self,
+                        Error::ExpectedEnumKey,
+                        visitor,
+                        deserialize_enum,
+                        name,
+                        variants
+                    )
                 }
-            } else {
-                Err(Error::ExpectedDict)
+            }
+            EnumRepr::Adjacent { tag, content } => {
+                if let Some(key) = dict.get_item(tag) {
+                    let val = dict.get_item(content);
+                    visitor.visit_enum(Enum::new(self, key, val))
+                } else {
+                    mismatch!(
+                        self,
+                        Error::ExpectedEnumKey,
+                        visitor,
+                        deserialize_enum,
+                        name,
+                        variants
+                    )
+                }
+            }
+            EnumRepr::Untagged => {
+                let err = Error::Unsupported(self.found());
+                mismatch!(self, err, visitor, deserialize_enum, name, variants)
             }
         }
     }
@@ -365,15 +897,63 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     }
 }
 
+thread_local! {
+    /// Classes used by `is_instance_of`, imported once per thread
+    static CLASSES: RefCell<HashMap<(&'static str, &'static str), PyObject>> =
+        RefCell::new(HashMap::new());
+}
+
+/// Check whether a value is an instance of an abstract base class from `collections.abc`
+fn is_abc_instance(py: Python, value: &PyAny, name: &'static str) -> Result<bool> {
+    is_instance_of(py, value, "collections.abc", name)
+}
+
+/// Check whether a value is an instance of the class `name` from `module`
+fn is_instance_of(
+    py: Python,
+    value: &PyAny,
+    module: &'static str,
+    name: &'static str,
+) -> Result<bool> {
+    let class = CLASSES.with(|classes| -> Result<PyObject> {
+        if let Some(class) = classes.borrow().get(&(module, name)) {
+            return Ok(class.clone_ref(py));
+        }
+        let class = PyObject::from(py.import(module)?.get(name)?);
+        classes
+            .borrow_mut()
+            .insert((module, name), class.clone_ref(py));
+        Ok(class)
+    })?;
+    match unsafe { pyo3::ffi::PyObject_IsInstance(value.as_ptr(), class.as_ptr()) } {
+        -1 => Err(PyErr::fetch(py).into()),
+        result => Ok(result == 1),
+    }
+}
+
+/// Describe a dict key for use in an error path
+fn key_segment(key: &PyAny) -> PathSegment {
+    match key.str() {
+        Ok(key) => PathSegment::Key(key.to_string_lossy().into_owned()),
+        Err(_) => PathSegment::Key("?".to_owned()),
+    }
+}
+
 struct SeqIter<'de> {
-    py: Python<'de>,
+    de: Deserializer<'de>,
     input: PyIterator<'de>,
+    index: usize,
     size: Option<usize>,
 }
 
 impl<'de> SeqIter<'de> {
-    fn new(py: Python<'de>, input: PyIterator<'de>, size: Option<usize>) -> Self {
-        Self { py, input, size }
+    fn new(de: &Deserializer<'de>, input: PyIterator<'de>, size: Option<usize>) -> Self {
+        Self {
+            de: de.clone(),
+            input,
+            index: 0,
+            size,
+        }
     }
 }
 
@@ -385,11 +965,14 @@ impl<'de, 'a: 'de> SeqAccess<'de> for SeqIter<'a> {
         T: DeserializeSeed<'de>,
     {
         if let Some(item) = self.input.next() {
+            let index = self.index;
+            self.index += 1;
             match item {
-                Ok(val) => seed
-                    .deserialize(&mut Deserializer::from_py(self.py.clone(), val))
+                Ok(val) => self
+                    .de
+                    .nested(val, || PathSegment::Index(index), |de| seed.deserialize(de))
                     .map(Some),
-                Err(_) => Err(Error::ExpectedListElement),
+                Err(_) => Err(Error::ExpectedListElement.at(PathSegment::Index(index))),
             }
         } else {
             Ok(None)
@@ -402,7 +985,7 @@ impl<'de, 'a: 'de> SeqAccess<'de> for SeqIter<'a> {
 }
 
 struct DictIter<'de> {
-    py: Python<'de>,
+    de: Deserializer<'de>,
     input: &'de PyDict,
     keys: &'de PyList,
     index: isize,
@@ -410,10 +993,10 @@ struct DictIter<'de> {
 }
 
 impl<'de> DictIter<'de> {
-    fn new(py: Python<'de>, input: &'de PyDict) -> Self {
+    fn new(de: &Deserializer<'de>, input: &'de PyDict) -> Self {
         let keys = input.keys();
         Self {
-            py,
+            de: de.clone(),
             input,
             keys,
             index: 0,
@@ -431,7 +1014,8 @@ impl<'de, 'a: 'de> MapAccess<'de> for DictIter<'a> {
     {
         if self.index < self.size {
             let key = self.keys.get_item(self.index);
-            seed.deserialize(&mut Deserializer::from_py(self.py.clone(), key))
+            self.de
+                .nested(key, || key_segment(key), |de| seed.deserialize(de))
                 .map(Some)
         } else {
             Ok(None)
@@ -444,23 +1028,175 @@ impl<'de, 'a: 'de> MapAccess<'de> for DictIter<'a> {
     {
         let idx = self.index;
         self.index += 1;
-        if let Some(item) = self.input.get_item(self.keys.get_item(idx)) {
-            seed.deserialize(&mut Deserializer::from_py(self.py.clone(), item))
+        let key = self.keys.get_item(idx);
+        if let Some(item) = self.input.get_item(key) {
+            self.de
+                .nested(item, || key_segment(key), |de| seed.deserialize(de))
         } else {
-            Err(Error::ExpectedDictValue)
+            Err(Error::ExpectedDictValue.at(key_segment(key)))
         }
     }
 }
 
+struct MappingIter<'de> {
+    de: Deserializer<'de>,
+    input: &'de PyAny,
+    iter: PyIterator<'de>,
+    items: bool,
+    size: Option<usize>,
+    key: Option<&'de PyAny>,
+    value: Option<&'de PyAny>,
+}
+
+impl<'de> MappingIter<'de> {
+    fn new(
+        de: &Deserializer<'de>,
+        iter: PyIterator<'de>,
+        items: bool,
+        size: Option<usize>,
+    ) -> Self {
+        Self {
+            de: de.clone(),
+            input: de.input,
+            iter,
+            items,
+            size,
+            key: None,
+            value: None,
+        }
+    }
+}
+
+impl<'de, 'a: 'de> MapAccess<'de> for MappingIter<'a> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        let item = match self.iter.next() {
+            Some(item) => item?,
+            None => return Ok(None),
+        };
+        let key = if self.items {
+            let (key, value): (&PyAny, &PyAny) = item.extract()?;
+            self.value = Some(value);
+            key
+        } else {
+            self.value = None;
+            item
+        };
+        self.key = Some(key);
+        self.de
+            .nested(key, || key_segment(key), |de| seed.deserialize(de))
+            .map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let key = self.key.take().ok_or(Error::ExpectedDictValue)?;
+        let value = match self.value.take() {
+            Some(value) => value,
+            None => self
+                .input
+                .get_item(key)
+                .map_err(|_| Error::ExpectedDictValue.at(key_segment(key)))?,
+        };
+        self.de
+            .nested(value, || key_segment(key), |de| seed.deserialize(de))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.size
+    }
+}
+
+struct AttrIter<'de> {
+    de: Deserializer<'de>,
+    input: &'de PyAny,
+    fields: &'static [&'static str],
+    index: usize,
+    value: Option<(&'static str, &'de PyAny)>,
+}
+
+impl<'de> AttrIter<'de> {
+    fn new(de: &Deserializer<'de>, fields: &'static [&'static str]) -> Self {
+        Self {
+            de: de.clone(),
+            input: de.input,
+            fields,
+            index: 0,
+            value: None,
+        }
+    }
+}
+
+impl<'de, 'a: 'de> MapAccess<'de> for AttrIter<'a> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        while let Some(&field) = self.fields.get(self.index) {
+            self.index += 1;
+            match self.input.getattr(field) {
+                Ok(value) => {
+                    self.value = Some((field, value));
+                    return seed.deserialize(field.into_deserializer()).map(Some);
+                }
+                // a missing attribute is treated as a missing key
+                Err(err) if err.is_instance::<AttributeError>(self.de.py) => (),
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let (field, value) = self.value.take().ok_or(Error::ExpectedDictValue)?;
+        self.de.nested(
+            value,
+            || PathSegment::Key(field.to_owned()),
+            |de| seed.deserialize(de),
+        )
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.fields.len() - self.index)
+    }
+}
+
 struct Enum<'de> {
-    py: Python<'de>,
+    de: Deserializer<'de>,
     key: &'de PyAny,
-    val: &'de PyAny,
+    val: Option<&'de PyAny>,
 }
 
 impl<'de> Enum<'de> {
-    fn new(py: Python<'de>, key: &'de PyAny, val: &'de PyAny) -> Self {
-        Self { py, key, val }
+    fn new(de: &Deserializer<'de>, key: &'de PyAny, val: Option<&'de PyAny>) -> Self {
+        Self {
+            de: de.clone(),
+            key,
+            val,
+        }
+    }
+
+    fn segment(&self) -> PathSegment {
+        match key_segment(self.key) {
+            PathSegment::Key(name) => PathSegment::Variant(name),
+            segment => segment,
+        }
+    }
+
+    fn value(&self) -> Result<&'de PyAny> {
+        self.val
+            .ok_or_else(|| Error::ExpectedEnumValue.at(self.segment()))
     }
 }
 
@@ -472,7 +1208,7 @@ impl<'de, 'a: 'de> EnumAccess<'de> for Enum<'a> {
     where
         V: DeserializeSeed<'de>,
     {
-        let val = seed.deserialize(&mut Deserializer::from_py(self.py, self.key))?;
+        let val = seed.deserialize(&mut self.de.child(self.key))?;
         Ok((val, self))
     }
 }
@@ -481,28 +1217,51 @@ impl<'de, 'a: 'de> VariantAccess<'de> for Enum<'a> {
     type Error = Error;
 
     fn unit_variant(self) -> Result<()> {
-        unimplemented!()
+        match (self.val, self.de.options.enum_repr) {
+            // remaining fields of an internally tagged unit variant are ignored
+            (_, EnumRepr::Internal { .. }) | (None, _) => Ok(()),
+            (Some(val), _) if val.is_none() => Ok(()),
+            (Some(val), _) => Err(Error::ExpectedNone(Found::new(val))),
+        }
     }
 
     fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
     where
         T: DeserializeSeed<'de>,
     {
-        seed.deserialize(&mut Deserializer::from_py(self.py, self.val))
+        let val = self.value()?;
+        self.de
+            .nested(val, || self.segment(), |de| seed.deserialize(de))
     }
 
     fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        de::Deserializer::deserialize_seq(&mut Deserializer::from_py(self.py, self.val), visitor)
+        if let EnumRepr::Internal { .. } = self.de.options.enum_repr {
+            return Err(Error::Message(
+                "internally tagged tuple variants are not supported".to_owned(),
+            )
+            .at(self.segment()));
+        }
+        let val = self.value()?;
+        self.de.nested(
+            val,
+            || self.segment(),
+            |value| de::Deserializer::deserialize_seq(value, visitor),
+        )
     }
 
     fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        de::Deserializer::deserialize_map(&mut Deserializer::from_py(self.py, self.val), visitor)
+        let val = self.value()?;
+        self.de.nested(
+            val,
+            || self.segment(),
+            |value| de::Deserializer::deserialize_map(value, visitor),
+        )
     }
 }
 
@@ -512,9 +1271,10 @@ impl<'de, 'a: 'de> VariantAccess<'de> for Enum<'a> {
 mod tests {
     use super::*;
     use crate::ser::to_py;
-    use pyo3::AsPyRef;
+    use pyo3::py_run;
+    use pyo3::{AsPyRef, ToPyObject};
     use serde_json::{self, json, Value as JsonValue};
-    use std::collections::HashMap;
+    use std::collections::{HashMap, HashSet};
     use std::iter::FromIterator;
 
     fn py_eval_into<'de, T: Deserialize<'de>>(py: Python<'de>, val: &str) -> T {
@@ -598,6 +1358,662 @@ mod tests {
         assert_eq!(result, E::Struct { a: 1 });
     }
 
+    #[test]
+    fn test_enum_repr() {
+        use crate::{to_py_with, SerializerOptions};
+        use serde::Serialize;
+
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        enum E {
+            Unit,
+            Newtype(u32),
+            Tuple(u32, u32),
+            Struct { a: u32 },
+        }
+
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let eval_with = |repr: EnumRepr, val: &str| -> Result<E> {
+            let input = py.eval(val, None, None).unwrap();
+            from_py_with(py, input, &DeserializerOptions::new().enum_repr(repr))
+        };
+
+        let internal = EnumRepr::Internal { tag: "type" };
+        assert_eq!(eval_with(internal, r#"{"type":"Unit"}"#).unwrap(), E::Unit);
+        assert_eq!(
+            eval_with(internal, r#"{"type":"Struct","a":1}"#).unwrap(),
+            E::Struct { a: 1 }
+        );
+        assert!(eval_with(internal, r#"{"a":1}"#).is_err());
+
+        let adjacent = EnumRepr::Adjacent {
+            tag: "t",
+            content: "c",
+        };
+        assert_eq!(eval_with(adjacent, r#"{"t":"Unit"}"#).unwrap(), E::Unit);
+        assert_eq!(
+            eval_with(adjacent, r#"{"t":"Newtype","c":1}"#).unwrap(),
+            E::Newtype(1)
+        );
+        assert_eq!(
+            eval_with(adjacent, r#"{"t":"Tuple","c":(1,2)}"#).unwrap(),
+            E::Tuple(1, 2)
+        );
+
+        assert_eq!(eval_with(EnumRepr::Untagged, r#""Unit""#).unwrap(), E::Unit);
+        assert!(eval_with(EnumRepr::Untagged, r#"{"a":1}"#).is_err());
+
+        let untagged = SerializerOptions::new().enum_repr(EnumRepr::Untagged);
+        let output = to_py_with(py, &E::Unit, &untagged).unwrap();
+        let options = DeserializerOptions::new().enum_repr(EnumRepr::Untagged);
+        let result: E = from_py_with(py, output.as_ref(py), &options).unwrap();
+        assert_eq!(result, E::Unit);
+    }
+
+    #[test]
+    fn test_error_path() {
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Order {
+            qty: u32,
+        }
+
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        enum E {
+            Struct { orders: Vec<Order> },
+        }
+
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        let input = py
+            .eval(r#"{"orders": [{"qty": 1}, {"qty": "x"}]}"#, None, None)
+            .unwrap();
+        let err = from_py::<HashMap<String, Vec<Order>>>(py, input).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "at .orders[1].qty: expected: integer, got 'str' ('x')"
+        );
+        assert_eq!(
+            err.path().unwrap().segments(),
+            &[
+                PathSegment::Key("orders".to_owned()),
+                PathSegment::Index(1),
+                PathSegment::Key("qty".to_owned()),
+            ]
+        );
+
+        let input = py
+            .eval(r#"{"Struct": {"orders": [{"qty": None}]}}"#, None, None)
+            .unwrap();
+        let err = from_py::<E>(py, input).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "at .Struct.orders[0].qty: expected: integer, got 'NoneType' (None)"
+        );
+    }
+
+    #[test]
+    fn test_found_type() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        let input = py
+            .eval("__import__('decimal').Decimal('1.5')", None, None)
+            .unwrap();
+        let err = from_py::<bool>(py, input).unwrap_err();
+        let found = err.found().unwrap();
+        assert_eq!(found.type_name, "decimal.Decimal");
+        assert_eq!(found.repr, "Decimal('1.5')");
+
+        let input = py.eval("'x' * 100", None, None).unwrap();
+        let err = from_py::<u32>(py, input).unwrap_err();
+        let found = err.found().unwrap();
+        assert_eq!(found.type_name, "str");
+        assert_eq!(found.repr, format!("'{}...", "x".repeat(39)));
+    }
+
+    #[test]
+    fn test_collect_errors() {
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Order {
+            qty: u32,
+            price: f64,
+            tags: Vec<String>,
+        }
+
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        let input = py
+            .eval(
+                r#"[{"qty": "x", "price": 1.5, "tags": []}, {"qty": 1, "price": None, "tags": [1]}]"#,
+                None,
+                None,
+            )
+            .unwrap();
+        let err = from_py_collect::<Vec<Order>>(py, input).unwrap_err();
+        let errors = match &err {
+            Error::Multiple(errors) => errors,
+            err => panic!("expected multiple errors, got {}", err),
+        };
+        let paths: Vec<String> = errors
+            .iter()
+            .map(|err| err.path().unwrap().to_string())
+            .collect();
+        assert_eq!(paths, vec!["[0].qty", "[1].price", "[1].tags[0]"]);
+        assert_eq!(errors[2].expected(), Some("string"));
+        assert_eq!(errors[2].found().unwrap().type_name, "int");
+
+        let input = py
+            .eval(r#"[{"qty": 1, "price": 2.0, "tags": ["a"]}]"#, None, None)
+            .unwrap();
+        assert_eq!(from_py_collect::<Vec<Order>>(py, input).unwrap().len(), 1);
+
+        let py_err: pyo3::PyErr = err.into();
+        let exc = py_err.to_object(py);
+        py_run!(
+            py,
+            exc,
+            r#"
+            assert isinstance(exc, TypeError)
+            assert [e["path"] for e in exc.errors] == ["[0].qty", "[1].price", "[1].tags[0]"]
+            assert exc.errors[0]["expected"] == "integer"
+            assert exc.errors[0]["actual"] == "str"
+        "#
+        );
+
+        // placeholders rejected by the visitor don't add errors of their own
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Counted {
+            count: std::num::NonZeroU32,
+        }
+        let input = py.eval(r#"[{"count": "x"}]"#, None, None).unwrap();
+        match from_py_collect::<Vec<Counted>>(py, input).unwrap_err() {
+            Error::Multiple(errors) => {
+                assert_eq!(errors.len(), 1);
+                assert_eq!(errors[0].path().unwrap().to_string(), "[0].count");
+                assert_eq!(errors[0].expected(), Some("integer"));
+            }
+            err => panic!("expected multiple errors, got {}", err),
+        }
+    }
+
+    #[test]
+    fn test_integer_overflow() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        let input = py.eval("300", None, None).unwrap();
+        let err = from_py::<u8>(py, input).unwrap_err();
+        assert_eq!(err.to_string(), "integer out of range for u8, got 300");
+        assert_eq!(err.expected(), Some("u8"));
+
+        let input = py.eval("[-1]", None, None).unwrap();
+        let err = from_py::<Vec<u32>>(py, input).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "at [0]: integer out of range for u32, got -1"
+        );
+        let exc = Into::<pyo3::PyErr>::into(err).to_object(py);
+        py_run!(py, exc, "assert isinstance(exc, OverflowError)");
+
+        let input = py.eval("'70000'", None, None).unwrap();
+        let lenient = DeserializerOptions::new().coercion(Coercion::Lenient);
+        let err = from_py_with::<i16>(py, input, &lenient).unwrap_err();
+        assert_eq!(err.to_string(), "integer out of range for i16, got 70000");
+
+        let input = py.eval("1.5", None, None).unwrap();
+        let err = from_py::<u8>(py, input).unwrap_err();
+        assert_eq!(err.expected(), Some("integer"));
+    }
+
+    #[test]
+    fn test_int128() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        let result: u128 = py_eval_into(py, "2**128 - 1");
+        assert_eq!(result, u128::max_value());
+        let result: i128 = py_eval_into(py, "-2**127");
+        assert_eq!(result, i128::min_value());
+
+        let result: JsonValue = py_eval_into(py, "[-1, 2**64 - 1]");
+        assert_eq!(result, json!([-1, u64::max_value()]));
+
+        let input = py.eval("[2**200]", None, None).unwrap();
+        assert!(from_py::<Vec<String>>(py, input).is_err());
+        let options = DeserializerOptions::new().big_int_strings(true);
+        let result: Vec<String> = from_py_with(py, input, &options).unwrap();
+        let expected: String = py_eval_into(py, "str(2**200)");
+        assert_eq!(result, vec![expected]);
+    }
+
+    #[test]
+    fn test_deserialize_any() {
+        use serde::Serialize;
+
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        #[serde(untagged)]
+        enum Untagged {
+            Int(i64),
+            Float(f64),
+            Text(String),
+            List(Vec<u32>),
+            Map(HashMap<String, u32>),
+        }
+
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Flattened {
+            id: i64,
+            #[serde(flatten)]
+            extra: HashMap<String, JsonValue>,
+        }
+
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        #[serde(tag = "type")]
+        enum Tagged {
+            Point { x: i32, y: i32 },
+        }
+
+        #[derive(PartialEq, Debug)]
+        struct Bytes(Vec<u8>);
+
+        impl<'de> Deserialize<'de> for Bytes {
+            fn deserialize<D: de::Deserializer<'de>>(
+                deserializer: D,
+            ) -> std::result::Result<Self, D::Error> {
+                struct BytesVisitor;
+
+                impl<'de> Visitor<'de> for BytesVisitor {
+                    type Value = Bytes;
+
+                    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                        formatter.write_str("bytes")
+                    }
+
+                    fn visit_bytes<E>(self, v: &[u8]) -> std::result::Result<Bytes, E> {
+                        Ok(Bytes(v.to_vec()))
+                    }
+                }
+
+                deserializer.deserialize_any(BytesVisitor)
+            }
+        }
+
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        for value in vec![
+            Untagged::Int(-5),
+            Untagged::Float(1.5),
+            Untagged::Text("a".to_owned()),
+            Untagged::List(vec![1, 2]),
+            Untagged::Map(HashMap::from_iter(vec![("a".to_owned(), 1)])),
+        ] {
+            let obj = to_py(py, &value).unwrap();
+            assert_eq!(from_py::<Untagged>(py, obj.as_ref(py)).unwrap(), value);
+        }
+        let result: Untagged = py_eval_into(py, "(1, 2)");
+        assert_eq!(result, Untagged::List(vec![1, 2]));
+        let result: Untagged = py_eval_into(py, "frozenset([3])");
+        assert_eq!(result, Untagged::List(vec![3]));
+        let result: Untagged = py_eval_into(py, "__import__('types').MappingProxyType({'a': 1})");
+        assert_eq!(
+            result,
+            Untagged::Map(HashMap::from_iter(vec![("a".to_owned(), 1)]))
+        );
+
+        let value = Flattened {
+            id: -1,
+            extra: HashMap::from_iter(vec![("a".to_owned(), json!([1, -2.5, "b"]))]),
+        };
+        let obj = to_py(py, &value).unwrap();
+        assert_eq!(from_py::<Flattened>(py, obj.as_ref(py)).unwrap(), value);
+
+        let value = Tagged::Point { x: -1, y: 2 };
+        let obj = to_py(py, &value).unwrap();
+        assert_eq!(from_py::<Tagged>(py, obj.as_ref(py)).unwrap(), value);
+
+        let result: Bytes = py_eval_into(py, "b'ab'");
+        assert_eq!(result, Bytes(b"ab".to_vec()));
+        let result: Bytes = py_eval_into(py, "bytearray(b'cd')");
+        assert_eq!(result, Bytes(b"cd".to_vec()));
+    }
+
+    #[test]
+    fn test_iterable() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        let result: HashSet<String> = py_eval_into(py, "{'a', 'b'}");
+        assert_eq!(
+            result,
+            HashSet::from_iter(vec!["a".to_owned(), "b".to_owned()])
+        );
+        let result: Vec<u32> = py_eval_into(py, "(x * 2 for x in range(3))");
+        assert_eq!(result, vec![0, 2, 4]);
+        let result: Vec<String> = py_eval_into(py, "{'k': 1}.keys()");
+        assert_eq!(result, vec!["k".to_owned()]);
+        let result: Vec<u32> = py_eval_into(py, "map(int, ['1', '2'])");
+        assert_eq!(result, vec![1, 2]);
+        let result: Vec<u32> = py_eval_into(
+            py,
+            "type('Hint', (), {'__iter__': lambda s: iter([5]), '__length_hint__': lambda s: 1 / 0})()",
+        );
+        assert_eq!(result, vec![5]);
+
+        let input = py.eval("1", None, None).unwrap();
+        assert!(from_py::<Vec<u32>>(py, input).is_err());
+
+        let strict = DeserializerOptions::new().coercion(Coercion::Strict);
+        let input = py.eval("frozenset(['ab'])", None, None).unwrap();
+        assert!(from_py_with::<Vec<String>>(py, input, &strict).is_ok());
+        let input = py.eval("b'ab'", None, None).unwrap();
+        assert!(from_py_with::<Vec<u8>>(py, input, &strict).is_err());
+    }
+
+    #[test]
+    fn test_mapping() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Test {
+            a: u32,
+            b: u32,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        enum E {
+            Newtype(u32),
+        }
+
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        let result: Test =
+            py_eval_into(py, "__import__('types').MappingProxyType({'a': 1, 'b': 2})");
+        assert_eq!(result, Test { a: 1, b: 2 });
+        let result: HashMap<String, u32> =
+            py_eval_into(py, "__import__('collections').ChainMap({'a': 1}, {'b': 2})");
+        assert_eq!(
+            result,
+            HashMap::from_iter(vec![("a".to_owned(), 1), ("b".to_owned(), 2)])
+        );
+
+        let locals = PyDict::new(py);
+        py.run(
+            r#"
+class KeysOnly:
+    def keys(self):
+        return ["a", "b"]
+    def __getitem__(self, key):
+        return {"a": 3, "b": "x"}[key]
+ret = KeysOnly()
+"#,
+            None,
+            Some(locals),
+        )
+        .unwrap();
+        let input = locals.get_item("ret").unwrap();
+        let err = from_py::<Test>(py, input).unwrap_err();
+        assert_eq!(err.to_string(), "at .b: expected: integer, got 'str' ('x')");
+
+        let result: E = py_eval_into(py, "__import__('types').MappingProxyType({'Newtype': 4})");
+        assert_eq!(result, E::Newtype(4));
+
+        // an attribute named `items` doesn't make an object a mapping
+        py.run(
+            r#"
+class Cart:
+    def __init__(self):
+        self.a = 5
+        self.b = 6
+        self.items = ["x"]
+ret = Cart()
+"#,
+            None,
+            Some(locals),
+        )
+        .unwrap();
+        let input = locals.get_item("ret").unwrap();
+        assert_eq!(from_py::<Test>(py, input).unwrap(), Test { a: 5, b: 6 });
+    }
+
+    #[test]
+    fn test_attributes() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Test {
+            a: u32,
+            #[serde(default)]
+            b: Vec<String>,
+            c: Option<u32>,
+        }
+
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let locals = PyDict::new(py);
+        py.run(
+            r#"
+from dataclasses import dataclass
+from types import SimpleNamespace
+
+@dataclass
+class Data:
+    a: int
+    b: list
+
+class Slots:
+    __slots__ = ("a", "c")
+    def __init__(self):
+        self.a = 2
+        self.c = 3
+
+class Both(dict):
+    def __init__(self):
+        super().__init__(a=4)
+        self.a = 5
+
+data = Data(1, ["x"])
+slots = Slots()
+plain = SimpleNamespace(a="y")
+both = Both()
+"#,
+            None,
+            Some(locals),
+        )
+        .unwrap();
+        let get = |name: &str| locals.get_item(name).unwrap();
+
+        let result: Test = from_py(py, get("data")).unwrap();
+        assert_eq!(
+            result,
+            Test {
+                a: 1,
+                b: vec!["x".to_owned()],
+                c: None
+            }
+        );
+        let result: Test = from_py(py, get("slots")).unwrap();
+        assert_eq!(
+            result,
+            Test {
+                a: 2,
+                b: vec![],
+                c: Some(3)
+            }
+        );
+        let err = from_py::<Test>(py, get("plain")).unwrap_err();
+        assert_eq!(err.to_string(), "at .a: expected: integer, got 'str' ('y')");
+
+        let result: Test = from_py(py, get("both")).unwrap();
+        assert_eq!(result.a, 4);
+        let options = DeserializerOptions::new().prefer_attributes(true);
+        let result: Test = from_py_with(py, get("both"), &options).unwrap();
+        assert_eq!(result.a, 5);
+    }
+
+    #[test]
+    fn test_positional() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Test {
+            id: u32,
+            name: String,
+        }
+
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        let result: Test = py_eval_into(py, r#"(1, "a")"#);
+        assert_eq!(
+            result,
+            Test {
+                id: 1,
+                name: "a".to_owned()
+            }
+        );
+        let result: Vec<Test> = py_eval_into(py, r#"[[2, "b"]]"#);
+        assert_eq!(result[0].id, 2);
+
+        let input = py.eval("(1,)", None, None).unwrap();
+        let err = from_py::<Test>(py, input).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid length 1, expected struct Test with 2 elements"
+        );
+        let input = py.eval(r#"(1, "a", 3)"#, None, None).unwrap();
+        assert!(from_py::<Test>(py, input).is_err());
+
+        let input = py.eval(r#"(1, 2)"#, None, None).unwrap();
+        let err = from_py::<Test>(py, input).unwrap_err();
+        assert_eq!(err.to_string(), "at [1]: expected: string, got 'int' (2)");
+    }
+
+    #[test]
+    fn test_namedtuple() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Point {
+            y: i32,
+            x: i32,
+        }
+
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let locals = PyDict::new(py);
+        py.run(
+            r#"
+from typing import NamedTuple
+
+class Point(NamedTuple):
+    x: int
+    y: int
+
+ret = Point(1, 2)
+"#,
+            None,
+            Some(locals),
+        )
+        .unwrap();
+        let result: Point = from_py(py, locals.get_item("ret").unwrap()).unwrap();
+        assert_eq!(result, Point { x: 1, y: 2 });
+    }
+
+    #[test]
+    fn test_python_enum() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        enum Color {
+            Red,
+            Green,
+            Blue,
+        }
+
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let locals = PyDict::new(py);
+        py.run(
+            r#"
+import enum
+
+class Color(enum.Enum):
+    Red = "Blue"
+    Green = "Red"
+
+class Level(enum.IntEnum):
+    Green = 1
+    Blue = 2
+"#,
+            None,
+            Some(locals),
+        )
+        .unwrap();
+        let eval = |expr: &str| py.eval(expr, None, Some(locals)).unwrap();
+
+        assert_eq!(from_py::<Color>(py, eval("Color.Red")).unwrap(), Color::Red);
+        let by_value = DeserializerOptions::new().match_enum_value(true);
+        assert_eq!(
+            from_py_with::<Color>(py, eval("Color.Green"), &by_value).unwrap(),
+            Color::Red
+        );
+        assert_eq!(
+            from_py_with::<Color>(py, eval("Level.Green"), &by_value).unwrap(),
+            Color::Green
+        );
+        assert_eq!(
+            from_py::<Color>(py, eval("Level.Blue")).unwrap(),
+            Color::Blue
+        );
+
+        // ints that aren't enum members don't select a variant
+        assert_eq!(
+            from_py::<Color>(py, eval("1")).unwrap_err().expected(),
+            Some("dict")
+        );
+        let input = eval("[1, 'Red']");
+        match from_py_collect::<Vec<Color>>(py, input).unwrap_err() {
+            Error::Multiple(errors) => assert_eq!(errors.len(), 1),
+            err => panic!("expected multiple errors, got {}", err),
+        }
+    }
+
+    #[test]
+    fn test_coercion() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let eval_with = |coercion: Coercion, val: &str| -> Option<JsonValue> {
+            let input = py.eval(val, None, None).unwrap();
+            let options = DeserializerOptions::new().coercion(coercion);
+            let int: Result<u32> = from_py_with(py, input, &options);
+            let float: Result<f64> = from_py_with(py, input, &options);
+            let seq: Result<Vec<String>> = from_py_with(py, input, &options);
+            int.map(|v| json!(v))
+                .or_else(|_| float.map(|v| json!(v)))
+                .or_else(|_| seq.map(|v| json!(v)))
+                .ok()
+        };
+
+        assert_eq!(eval_with(Coercion::Default, "True"), Some(json!(1)));
+        assert_eq!(eval_with(Coercion::Strict, "True"), None);
+        assert_eq!(
+            eval_with(Coercion::Default, "'ab'"),
+            Some(json!(["a", "b"]))
+        );
+        assert_eq!(eval_with(Coercion::Strict, "'ab'"), None);
+        assert_eq!(eval_with(Coercion::Strict, "['ab']"), Some(json!(["ab"])));
+
+        let input = py.eval("1", None, None).unwrap();
+        let strict = DeserializerOptions::new().coercion(Coercion::Strict);
+        assert!(from_py_with::<f64>(py, input, &strict).is_err());
+        assert!((from_py::<f64>(py, input).unwrap() - 1.0).abs() < std::f64::EPSILON);
+
+        assert_eq!(eval_with(Coercion::Lenient, "' 42'"), Some(json!(42)));
+        assert_eq!(eval_with(Coercion::Lenient, "'1.5'"), Some(json!(1.5)));
+        assert_eq!(
+            eval_with(Coercion::Default, "'42'"),
+            Some(json!(["4", "2"]))
+        );
+    }
+
     #[test]
     fn test_json() {
         let jsonval: JsonValue = json!({
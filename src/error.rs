@@ -1,34 +1,213 @@
-use std;
 use std::fmt::{self, Display};
 
-use pyo3::{exceptions::Exception, exceptions::TypeError, PyErr, PyResult};
+use pyo3::types::{PyDict, PyList};
+use pyo3::{ObjectProtocol, PyAny, PyErr, PyResult, Python};
 use serde::{de, ser};
 
+use super::exceptions;
+
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// A step taken to reach a nested value
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+    Variant(String),
+}
+
+/// The location of a value inside the deserialized input
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Path(Vec<PathSegment>);
+
+impl Path {
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.0
+    }
+}
+
+impl Display for Path {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        for segment in &self.0 {
+            match segment {
+                PathSegment::Key(key) | PathSegment::Variant(key) => write!(formatter, ".{}", key)?,
+                PathSegment::Index(index) => write!(formatter, "[{}]", index)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Longest `repr` of an unexpected value kept for error messages
+const MAX_REPR_LEN: usize = 40;
+
+/// The Python value found in place of an expected type
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Found {
+    pub type_name: String,
+    pub repr: String,
+}
+
+impl Found {
+    pub fn new(value: &PyAny) -> Self {
+        let ty = value.get_type();
+        let attr = |name: &str| ty.getattr(name).and_then(|v| v.extract::<String>()).ok();
+        let type_name = match (attr("__module__"), attr("__qualname__")) {
+            (Some(ref module), Some(qualname)) if module != "builtins" => {
+                format!("{}.{}", module, qualname)
+            }
+            (_, Some(qualname)) => qualname,
+            _ => ty.name().into_owned(),
+        };
+        let mut repr = match value.repr() {
+            Ok(repr) => repr.to_string_lossy().into_owned(),
+            Err(_) => "<unknown>".to_owned(),
+        };
+        if let Some((idx, _)) = repr.char_indices().nth(MAX_REPR_LEN) {
+            repr.truncate(idx);
+            repr.push_str("...");
+        }
+        Found { type_name, repr }
+    }
+}
+
+impl Display for Found {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "'{}' ({})", self.type_name, self.repr)
+    }
+}
+
 #[derive(Debug)]
 pub enum Error {
     Message(String),
+    Serialization(String),
     PyErr(PyErr),
-    ExpectedBoolean,
-    ExpectedBytes,
-    ExpectedChar,
-    ExpectedDict,
+    At(Path, Box<Error>),
+    Multiple(Vec<Error>),
+    ExpectedBoolean(Found),
+    ExpectedBytes(Found),
+    ExpectedChar(Found),
+    ExpectedDict(Found),
     ExpectedDictValue,
     ExpectedEnumKey,
     ExpectedEnumValue,
-    ExpectedFloat,
-    ExpectedInteger,
-    ExpectedList,
+    ExpectedFloat(Found),
+    ExpectedInteger(Found),
+    ExpectedList(Found),
     ExpectedListElement,
-    ExpectedNone,
-    ExpectedString,
-    Unsupported,
+    ExpectedNone(Found),
+    ExpectedString(Found),
+    IntegerOverflow {
+        bits: u32,
+        signed: bool,
+        value: String,
+    },
+    Unsupported(Found),
+}
+
+impl Error {
+    /// Record that this error occurred below the given path segment
+    pub fn at(self, segment: PathSegment) -> Self {
+        match self {
+            Error::At(mut path, err) => {
+                path.0.insert(0, segment);
+                Error::At(path, err)
+            }
+            Error::Multiple(errors) => Error::Multiple(
+                errors
+                    .into_iter()
+                    .map(|err| err.at(segment.clone()))
+                    .collect(),
+            ),
+            err => Error::At(Path(vec![segment]), Box::new(err)),
+        }
+    }
+
+    /// Whether deserialization may continue past this error when collecting errors
+    pub fn is_recoverable(&self) -> bool {
+        match self {
+            Error::At(_, err) => err.is_recoverable(),
+            Error::PyErr(_) | Error::Multiple(_) | Error::ExpectedListElement => false,
+            _ => true,
+        }
+    }
+
+    /// The Python type which was expected, for type mismatch errors
+    pub fn expected(&self) -> Option<&'static str> {
+        Some(match self {
+            Error::At(_, err) => return err.expected(),
+            Error::ExpectedBoolean(_) => "boolean",
+            Error::ExpectedBytes(_) => "bytes",
+            Error::ExpectedChar(_) => "single character",
+            Error::ExpectedDict(_) => "dict",
+            Error::ExpectedDictValue => "dict value",
+            Error::ExpectedEnumKey => "non-empty dict",
+            Error::ExpectedEnumValue => "non-empty dict value",
+            Error::ExpectedFloat(_) => "float",
+            Error::ExpectedInteger(_) => "integer",
+            Error::ExpectedList(_) => "list",
+            Error::ExpectedListElement => "list element",
+            Error::ExpectedNone(_) => "none",
+            Error::ExpectedString(_) => "string",
+            Error::IntegerOverflow { bits, signed, .. } => integer_type(*bits, *signed),
+            _ => return None,
+        })
+    }
+
+    /// The Python value which did not match the expected type
+    pub fn found(&self) -> Option<&Found> {
+        match self {
+            Error::At(_, err) => err.found(),
+            Error::ExpectedBoolean(found)
+            | Error::ExpectedBytes(found)
+            | Error::ExpectedChar(found)
+            | Error::ExpectedDict(found)
+            | Error::ExpectedFloat(found)
+            | Error::ExpectedInteger(found)
+            | Error::ExpectedList(found)
+            | Error::ExpectedNone(found)
+            | Error::ExpectedString(found)
+            | Error::Unsupported(found) => Some(found),
+            _ => None,
+        }
+    }
+
+    fn is_py_err(&self) -> bool {
+        match self {
+            Error::PyErr(_) => true,
+            _ => false,
+        }
+    }
+
+    /// The location of the failing value, if it was nested inside the input
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Error::At(path, _) => Some(path),
+            _ => None,
+        }
+    }
+}
+
+/// Name the Rust integer type with the given width and signedness
+fn integer_type(bits: u32, signed: bool) -> &'static str {
+    match (bits, signed) {
+        (8, true) => "i8",
+        (16, true) => "i16",
+        (32, true) => "i32",
+        (64, true) => "i64",
+        (128, true) => "i128",
+        (8, false) => "u8",
+        (16, false) => "u16",
+        (32, false) => "u32",
+        (64, false) => "u64",
+        (128, false) => "u128",
+        _ => "integer",
+    }
 }
 
 impl ser::Error for Error {
     fn custom<T: Display>(msg: T) -> Self {
-        Error::Message(msg.to_string())
+        Error::Serialization(msg.to_string())
     }
 }
 
@@ -40,25 +219,37 @@ impl de::Error for Error {
 
 impl Display for Error {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        let msg = match self {
-            Error::Message(msg) => msg,
-            Error::PyErr(err) => return write!(formatter, "{:?}", err),
-            Error::ExpectedBoolean => "expected: boolean",
-            Error::ExpectedBytes => "expected: bytes",
-            Error::ExpectedChar => "expected: single character",
-            Error::ExpectedDict => "expected: dict",
-            Error::ExpectedDictValue => "expected: dict value",
-            Error::ExpectedEnumKey => "expected: non-empty dict",
-            Error::ExpectedEnumValue => "expected: non-empty dict value",
-            Error::ExpectedFloat => "expected: float",
-            Error::ExpectedInteger => "expected: integer",
-            Error::ExpectedList => "expected: list",
-            Error::ExpectedListElement => "expected: list element",
-            Error::ExpectedNone => "expected: none",
-            Error::ExpectedString => "expected: string",
-            Error::Unsupported => "unsupported input value",
-        };
-        formatter.write_str(msg)
+        match self {
+            Error::Message(msg) | Error::Serialization(msg) => formatter.write_str(msg)?,
+            Error::PyErr(err) => write!(formatter, "{:?}", err)?,
+            Error::At(path, err) => write!(formatter, "at {}: {}", path, err)?,
+            Error::Multiple(errors) => {
+                write!(formatter, "{} errors", errors.len())?;
+                for err in errors {
+                    write!(formatter, "\n  {}", err)?;
+                }
+            }
+            Error::Unsupported(found) => {
+                write!(formatter, "unsupported input value, got {}", found)?
+            }
+            Error::IntegerOverflow {
+                bits,
+                signed,
+                value,
+            } => write!(
+                formatter,
+                "integer out of range for {}, got {}",
+                integer_type(*bits, *signed),
+                value
+            )?,
+            err => {
+                write!(formatter, "expected: {}", err.expected().unwrap_or("value"))?;
+                if let Some(found) = err.found() {
+                    write!(formatter, ", got {}", found)?;
+                }
+            }
+        }
+        Ok(())
     }
 }
 
@@ -74,10 +265,52 @@ impl Into<PyErr> for Error {
     fn into(self) -> PyErr {
         match self {
             Error::PyErr(err) => err,
-            Error::Message(msg) => Exception::py_err(msg),
-            err => TypeError::py_err(err.to_string()),
+            Error::At(_, err) if err.is_py_err() => (*err).into(),
+            err => {
+                let gil = Python::acquire_gil();
+                new_py_err(gil.python(), &err).unwrap_or_else(|err| err)
+            }
+        }
+    }
+}
+
+/// Build a serde-pyo3 exception describing the error in its attributes
+fn new_py_err(py: Python, err: &Error) -> PyResult<PyErr> {
+    let mut inner = err;
+    while let Error::At(_, err) = inner {
+        inner = &**err;
+    }
+    let ty = match inner {
+        Error::Serialization(_) => exceptions::serialization_error(py)?,
+        Error::IntegerOverflow { .. } => exceptions::overflow_error(py)?,
+        _ => exceptions::deserialization_error(py)?,
+    };
+    let exc = ty.call1((err.to_string(),))?;
+    for (key, value) in error_details(py, err)? {
+        exc.setattr(key.extract::<&str>()?, value)?;
+    }
+    if let Error::Multiple(errors) = err {
+        let details = PyList::empty(py);
+        for err in errors {
+            details.append(error_details(py, err)?)?;
         }
+        exc.setattr("errors", details)?;
+    }
+    Ok(PyErr::from_instance(exc))
+}
+
+/// Describe a single error as a Python dict
+fn error_details<'p>(py: Python<'p>, err: &Error) -> PyResult<&'p PyDict> {
+    let mut inner = err;
+    while let Error::At(_, err) = inner {
+        inner = &**err;
     }
+    let details = PyDict::new(py);
+    details.set_item("path", err.path().map(|path| path.to_string()))?;
+    details.set_item("expected", err.expected())?;
+    details.set_item("actual", err.found().map(|found| found.type_name.clone()))?;
+    details.set_item("message", inner.to_string())?;
+    Ok(details)
 }
 
 pub trait ResultExt<T> {
@@ -0,0 +1,149 @@
+use std::os::raw::c_char;
+use std::ptr;
+
+use pyo3::exceptions::{Exception, OverflowError, TypeError, ValueError};
+use pyo3::types::{PyModule, PyTuple, PyType};
+use pyo3::{ffi, AsPyPointer, PyErr, PyResult, Python, ToPyObject};
+
+static mut SERDE_PYO3_ERROR: *mut ffi::PyObject = ptr::null_mut();
+static mut DESERIALIZATION_ERROR: *mut ffi::PyObject = ptr::null_mut();
+static mut SERIALIZATION_ERROR: *mut ffi::PyObject = ptr::null_mut();
+static mut OVERFLOW_ERROR: *mut ffi::PyObject = ptr::null_mut();
+
+/// Create an exception class on first use, the GIL guards access to `slot`
+unsafe fn exception_type<'p>(
+    py: Python<'p>,
+    slot: *mut *mut ffi::PyObject,
+    name: &'static str,
+    bases: &PyTuple,
+) -> PyResult<&'p PyType> {
+    if (*slot).is_null() {
+        let ty = ffi::PyErr_NewException(
+            name.as_ptr() as *mut c_char,
+            bases.as_ptr(),
+            ptr::null_mut(),
+        );
+        if ty.is_null() {
+            return Err(PyErr::fetch(py));
+        }
+        *slot = ty;
+    }
+    Ok(py.from_borrowed_ptr(*slot))
+}
+
+/// Base class of every exception raised for a serde-pyo3 error
+pub fn serde_pyo3_error(py: Python) -> PyResult<&PyType> {
+    let bases = PyTuple::new(py, &[py.get_type::<Exception>()]);
+    unsafe {
+        exception_type(
+            py,
+            &mut SERDE_PYO3_ERROR,
+            "serde_pyo3.SerdePyo3Error\0",
+            bases,
+        )
+    }
+}
+
+/// Raised when a Python value cannot be deserialized, also a `TypeError` and `ValueError`
+pub fn deserialization_error(py: Python) -> PyResult<&PyType> {
+    let bases = PyTuple::new(
+        py,
+        vec![
+            serde_pyo3_error(py)?.to_object(py),
+            py.get_type::<TypeError>().to_object(py),
+            py.get_type::<ValueError>().to_object(py),
+        ],
+    );
+    unsafe {
+        exception_type(
+            py,
+            &mut DESERIALIZATION_ERROR,
+            "serde_pyo3.DeserializationError\0",
+            bases,
+        )
+    }
+}
+
+/// Raised when a Python integer is out of range, also an `OverflowError`
+pub fn overflow_error(py: Python) -> PyResult<&PyType> {
+    let bases = PyTuple::new(
+        py,
+        vec![
+            deserialization_error(py)?.to_object(py),
+            py.get_type::<OverflowError>().to_object(py),
+        ],
+    );
+    unsafe {
+        exception_type(
+            py,
+            &mut OVERFLOW_ERROR,
+            "serde_pyo3.IntegerOverflowError\0",
+            bases,
+        )
+    }
+}
+
+/// Raised when a Rust value cannot be serialized
+pub fn serialization_error(py: Python) -> PyResult<&PyType> {
+    let bases = PyTuple::new(py, &[serde_pyo3_error(py)?]);
+    unsafe {
+        exception_type(
+            py,
+            &mut SERIALIZATION_ERROR,
+            "serde_pyo3.SerializationError\0",
+            bases,
+        )
+    }
+}
+
+/// Add the serde-pyo3 exception classes to a Python module
+pub fn register_exceptions(py: Python, module: &PyModule) -> PyResult<()> {
+    module.add("SerdePyo3Error", serde_pyo3_error(py)?.to_object(py))?;
+    module.add(
+        "DeserializationError",
+        deserialization_error(py)?.to_object(py),
+    )?;
+    module.add("IntegerOverflowError", overflow_error(py)?.to_object(py))?;
+    module.add("SerializationError", serialization_error(py)?.to_object(py))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_py, ResultExt};
+    use pyo3::py_run;
+
+    #[test]
+    fn test_register_exceptions() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        let module = PyModule::new(py, "serde_pyo3").unwrap();
+        register_exceptions(py, module).unwrap();
+
+        let input = py.eval(r#"{"a": "x"}"#, None, None).unwrap();
+        let err = from_py::<std::collections::HashMap<String, u32>>(py, input)
+            .to_py_result()
+            .unwrap_err();
+        let exc = err.to_object(py);
+        py_run!(
+            py,
+            module exc,
+            r#"
+            assert issubclass(module.DeserializationError, module.SerdePyo3Error)
+            assert issubclass(module.DeserializationError, TypeError)
+            assert issubclass(module.DeserializationError, ValueError)
+            assert issubclass(module.SerializationError, module.SerdePyo3Error)
+            assert isinstance(exc, module.DeserializationError)
+            assert exc.path == ".a"
+            assert exc.expected == "integer"
+            assert exc.actual == "str"
+            assert exc.message == "expected: integer, got 'str' ('x')"
+            assert str(exc) == "at .a: expected: integer, got 'str' ('x')"
+            assert issubclass(module.IntegerOverflowError, module.DeserializationError)
+            assert issubclass(module.IntegerOverflowError, OverflowError)
+        "#
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use std::io;
+
+use pyo3::{PyAny, PyObject, Python};
+use serde::ser::Serialize;
+use serde_json::ser::{CompactFormatter, Formatter, PrettyFormatter};
+
+use super::error::{Error, Result};
+use super::value::{py_from_deserializer, PySerialize};
+
+/// Options controlling the output of `dumps_with`
+#[derive(Clone, Debug, Default)]
+pub struct JsonOptions {
+    indent: Option<usize>,
+    sort_keys: bool,
+    ensure_ascii: bool,
+}
+
+impl JsonOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pretty-print with the given number of spaces per level
+    pub fn indent(mut self, indent: usize) -> Self {
+        self.indent = Some(indent);
+        self
+    }
+
+    /// Sort the keys of dicts
+    pub fn sort_keys(mut self, sort: bool) -> Self {
+        self.sort_keys = sort;
+        self
+    }
+
+    /// Escape all non-ASCII characters in strings
+    pub fn ensure_ascii(mut self, ensure: bool) -> Self {
+        self.ensure_ascii = ensure;
+        self
+    }
+}
+
+/// Encode a Python object as a JSON string
+pub fn dumps(py: Python, obj: &PyAny) -> Result<String> {
+    dumps_with(py, obj, &JsonOptions::default())
+}
+
+/// Encode a Python object as a JSON string with the given options
+pub fn dumps_with(_py: Python, obj: &PyAny, options: &JsonOptions) -> Result<String> {
+    let mut output = Vec::new();
+    let indent = vec![b' '; options.indent.unwrap_or(0)];
+    let result = match (options.indent, options.ensure_ascii) {
+        (Some(_), false) => write(
+            &mut output,
+            obj,
+            options,
+            PrettyFormatter::with_indent(&indent),
+        ),
+        (Some(_), true) => write(
+            &mut output,
+            obj,
+            options,
+            AsciiFormatter(PrettyFormatter::with_indent(&indent)),
+        ),
+        (None, false) => write(&mut output, obj, options, CompactFormatter),
+        (None, true) => write(&mut output, obj, options, AsciiFormatter(CompactFormatter)),
+    };
+    result.map_err(|err| Error::Serialization(err.to_string()))?;
+    // serde_json only writes valid UTF-8
+    Ok(String::from_utf8(output).expect("invalid UTF-8 in JSON output"))
+}
+
+/// Decode a JSON string into Python objects
+pub fn loads(py: Python, input: &str) -> Result<PyObject> {
+    let mut deserializer = serde_json::Deserializer::from_str(input);
+    py_from_deserializer(py, &mut deserializer)
+        .and_then(|obj| deserializer.end().map(|_| obj))
+        .map_err(|err| Error::Message(err.to_string()))
+}
+
+fn write<F: Formatter>(
+    output: &mut Vec<u8>,
+    obj: &PyAny,
+    options: &JsonOptions,
+    formatter: F,
+) -> serde_json::Result<()> {
+    let mut serializer = serde_json::Serializer::with_formatter(output, formatter);
+    PySerialize::new(obj)
+        .sort_keys(options.sort_keys)
+        .serialize(&mut serializer)
+}
+
+/// Wraps a JSON formatter to escape non-ASCII characters as `\uXXXX`
+struct AsciiFormatter<F>(F);
+
+impl<F: Formatter> Formatter for AsciiFormatter<F> {
+    fn write_string_fragment<W>(&mut self, writer: &mut W, fragment: &str) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        let mut start = 0;
+        for (idx, c) in fragment.char_indices() {
+            if c.is_ascii() {
+                continue;
+            }
+            writer.write_all(fragment[start..idx].as_bytes())?;
+            let mut units = [0u16; 2];
+            for unit in c.encode_utf16(&mut units) {
+                write!(writer, "\\u{:04x}", unit)?;
+            }
+            start = idx + c.len_utf8();
+        }
+        writer.write_all(fragment[start..].as_bytes())
+    }
+
+    fn begin_array<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.0.begin_array(writer)
+    }
+
+    fn end_array<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.0.end_array(writer)
+    }
+
+    fn begin_array_value<W>(&mut self, writer: &mut W, first: bool) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.0.begin_array_value(writer, first)
+    }
+
+    fn end_array_value<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.0.end_array_value(writer)
+    }
+
+    fn begin_object<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.0.begin_object(writer)
+    }
+
+    fn end_object<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.0.end_object(writer)
+    }
+
+    fn begin_object_key<W>(&mut self, writer: &mut W, first: bool) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.0.begin_object_key(writer, first)
+    }
+
+    fn begin_object_value<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.0.begin_object_value(writer)
+    }
+
+    fn end_object_value<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.0.end_object_value(writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::py_run;
+
+    #[test]
+    fn test_dumps() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let obj = py
+            .eval(r#"{"b": [1, 2.5, None], "a": {"é": True}}"#, None, None)
+            .unwrap();
+
+        assert_eq!(
+            dumps(py, obj).unwrap(),
+            r#"{"b":[1,2.5,null],"a":{"é":true}}"#
+        );
+        let options = JsonOptions::new().sort_keys(true).ensure_ascii(true);
+        assert_eq!(
+            dumps_with(py, obj, &options).unwrap(),
+            r#"{"a":{"\u00e9":true},"b":[1,2.5,null]}"#
+        );
+        let options = JsonOptions::new().indent(2).sort_keys(true);
+        assert_eq!(
+            dumps_with(py, obj, &options).unwrap(),
+            "{\n  \"a\": {\n    \"é\": true\n  },\n  \"b\": [\n    1,\n    2.5,\n    null\n  ]\n}"
+        );
+
+        let obj = py.eval("'\\U0001F600'", None, None).unwrap();
+        let options = JsonOptions::new().ensure_ascii(true);
+        assert_eq!(dumps_with(py, obj, &options).unwrap(), r#""\ud83d\ude00""#);
+    }
+
+    #[test]
+    fn test_loads() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let obj = loads(py, r#"{"a": [1, "x", null], "b": 1.5}"#).unwrap();
+        py_run!(py, obj, r#"assert obj == {"a": [1, "x", None], "b": 1.5}"#);
+
+        assert!(loads(py, "[1] 2").is_err());
+        assert!(loads(py, "{").is_err());
+    }
+}
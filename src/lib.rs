@@ -1,10 +1,31 @@
+mod array;
+mod columnar;
 mod de;
 mod error;
+mod exceptions;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod literal;
+mod options;
+mod pickle;
+mod placeholder;
+mod pyany;
 mod ser;
+mod value;
 
-pub use de::{from_py, Deserializer};
-pub use error::{Error, Result, ResultExt};
-pub use ser::{to_py, Serializer};
+pub use array::{ArrayElement, NumericArray};
+pub use columnar::{from_py_columnar, from_py_columnar_with, to_py_columnar, to_py_columnar_with};
+pub use de::{from_py, from_py_collect, from_py_with, Deserializer};
+pub use error::{Error, Path, PathSegment, Result, ResultExt};
+pub use exceptions::{
+    deserialization_error, overflow_error, register_exceptions, serde_pyo3_error,
+    serialization_error,
+};
+pub use options::{Coercion, DeserializerOptions, EnumRepr, SeqRepr, SerializerOptions, UnitRepr};
+pub use pickle::{from_pickle, to_pickle};
+pub use pyany::PyAnyField;
+pub use ser::{to_py, to_py_with, Serializer};
+pub use value::{py_from_deserializer, PyObjectSeed, PySerialize};
 
 use pyo3::{FromPyObject, PyAny, PyResult, Python};
 
@@ -0,0 +1,1219 @@
+use std::fmt::Write;
+
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, VariantAccess, Visitor,
+};
+use serde::{forward_to_deserialize_any, ser, Serialize};
+
+use super::error::{Error, Result};
+
+/// Write a value as Python literal source text, readable by `ast.literal_eval`
+pub fn to_string<T>(value: &T) -> Result<String>
+where
+    T: ?Sized + Serialize,
+{
+    let mut serializer = LiteralSerializer {
+        output: String::new(),
+    };
+    value.serialize(&mut serializer)?;
+    Ok(serializer.output)
+}
+
+/// Read a value from Python literal source text, as accepted by `ast.literal_eval`
+pub fn from_str<T>(input: &str) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    let mut parser = Parser {
+        input,
+        pos: 0,
+        depth: 0,
+    };
+    let literal = parser.parse_value()?;
+    parser.skip_whitespace();
+    if parser.pos < input.len() {
+        return Err(parser.error("unexpected trailing characters"));
+    }
+    T::deserialize(literal)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+struct LiteralSerializer {
+    output: String,
+}
+
+impl LiteralSerializer {
+    fn write_str(&mut self, value: &str) {
+        self.output.push('\'');
+        for c in value.chars() {
+            match c {
+                '\\' => self.output.push_str("\\\\"),
+                '\'' => self.output.push_str("\\'"),
+                '\n' => self.output.push_str("\\n"),
+                '\r' => self.output.push_str("\\r"),
+                '\t' => self.output.push_str("\\t"),
+                // control characters are all below U+0100
+                c if c.is_control() => write!(self.output, "\\x{:02x}", c as u32).unwrap(),
+                c => self.output.push(c),
+            }
+        }
+        self.output.push('\'');
+    }
+
+    fn write_bytes(&mut self, value: &[u8]) {
+        self.output.push_str("b'");
+        for &b in value {
+            match b {
+                b'\\' => self.output.push_str("\\\\"),
+                b'\'' => self.output.push_str("\\'"),
+                b'\n' => self.output.push_str("\\n"),
+                b'\r' => self.output.push_str("\\r"),
+                b'\t' => self.output.push_str("\\t"),
+                b if b == b' ' || b.is_ascii_graphic() => self.output.push(b as char),
+                b => write!(self.output, "\\x{:02x}", b).unwrap(),
+            }
+        }
+        self.output.push('\'');
+    }
+
+    /// Open a compound value, closing it with `close` when done
+    fn compound(&mut self, open: &str, close: &'static str, tuple: bool) -> Compound {
+        self.output.push_str(open);
+        Compound {
+            ser: self,
+            count: 0,
+            close,
+            tuple,
+        }
+    }
+
+    /// Open a dict holding a single variant, such as `{'Variant': ` for `to_py` compatibility
+    fn open_variant(&mut self, variant: &str) {
+        self.output.push('{');
+        self.write_str(variant);
+        self.output.push_str(": ");
+    }
+}
+
+struct Compound<'a> {
+    ser: &'a mut LiteralSerializer,
+    count: usize,
+    close: &'static str,
+    tuple: bool,
+}
+
+impl<'a> Compound<'a> {
+    fn separator(&mut self) {
+        if self.count > 0 {
+            self.ser.output.push_str(", ");
+        }
+        self.count += 1;
+    }
+
+    fn element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.separator();
+        value.serialize(&mut *self.ser)
+    }
+
+    fn field<T>(&mut self, key: &str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.separator();
+        self.ser.write_str(key);
+        self.ser.output.push_str(": ");
+        value.serialize(&mut *self.ser)
+    }
+
+    fn finish(self) -> Result<()> {
+        if self.tuple && self.count == 1 {
+            self.ser.output.push(',');
+        }
+        self.ser.output.push_str(self.close);
+        Ok(())
+    }
+}
+
+impl<'a> ser::Serializer for &'a mut LiteralSerializer {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Compound<'a>;
+    type SerializeTuple = Compound<'a>;
+    type SerializeTupleStruct = Compound<'a>;
+    type SerializeTupleVariant = Compound<'a>;
+    type SerializeMap = Compound<'a>;
+    type SerializeStruct = Compound<'a>;
+    type SerializeStructVariant = Compound<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.output.push_str(if v { "True" } else { "False" });
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        write!(self.output, "{}", v).unwrap();
+        Ok(())
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        write!(self.output, "{}", v).unwrap();
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        write!(self.output, "{}", v).unwrap();
+        Ok(())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        write!(self.output, "{}", v).unwrap();
+        Ok(())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        self.serialize_f64(f64::from(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        if v.is_nan() {
+            return Err(Error::Serialization(
+                "NaN has no Python literal representation".to_owned(),
+            ));
+        }
+        if v.is_infinite() {
+            // literal_eval reads an out of range exponent as infinity
+            self.output
+                .push_str(if v > 0.0 { "1e999" } else { "-1e999" });
+        } else {
+            write!(self.output, "{:?}", v).unwrap();
+        }
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.write_str(v.encode_utf8(&mut [0u8; 4]));
+        Ok(())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.write_str(v);
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        self.write_bytes(v);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        self.output.push_str("None");
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.write_str(variant);
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.open_variant(variant);
+        value.serialize(&mut *self)?;
+        self.output.push('}');
+        Ok(())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'a>> {
+        Ok(self.compound("[", "]", false))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Compound<'a>> {
+        Ok(self.compound("(", ")", true))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Compound<'a>> {
+        Ok(self.compound("(", ")", true))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>> {
+        self.open_variant(variant);
+        Ok(self.compound("(", ")}", true))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Compound<'a>> {
+        Ok(self.compound("{", "}", false))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Compound<'a>> {
+        Ok(self.compound("{", "}", false))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>> {
+        self.open_variant(variant);
+        Ok(self.compound("{", "}}", false))
+    }
+}
+
+impl<'a> ser::SerializeSeq for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeTuple for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeTupleStruct for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeTupleVariant for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeMap for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(key)
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.ser.output.push_str(": ");
+        value.serialize(&mut *self.ser)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeStruct for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeStructVariant for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// A Python value built from literal text or decoded from a pickle
+#[derive(Clone, Debug)]
+pub(crate) enum Literal {
+    None,
+    Bool(bool),
+    Int(i128),
+    UInt(u128),
+    Float(f64),
+    Str(String),
+    Bytes(Vec<u8>),
+    List(Vec<Literal>),
+    Tuple(Vec<Literal>),
+    Set(Vec<Literal>),
+    Dict(Vec<(Literal, Literal)>),
+}
+
+/// Deepest nesting of containers accepted in literal text or a pickle
+pub(crate) const MAX_DEPTH: usize = 128;
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, msg: &str) -> Error {
+        let before = &self.input[..self.pos];
+        let line = before.matches('\n').count() + 1;
+        let column = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+        Error::Message(format!("{} at line {} column {}", msg, line, column))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.input[self.pos..].chars().nth(offset)
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' | '\n' | '\r' | '\x0c' => {
+                    self.next();
+                }
+                '\\' if self.peek_at(1) == Some('\n') => {
+                    self.pos += 2;
+                }
+                '#' => {
+                    while let Some(c) = self.next() {
+                        if c == '\n' {
+                            break;
+                        }
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        self.skip_whitespace();
+        if self.next() == Some(expected) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected)))
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Literal> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('[') | Some('(') | Some('{') => {
+                if self.depth == MAX_DEPTH {
+                    return Err(self.error("nesting too deep"));
+                }
+                self.depth += 1;
+                let result = self.parse_container();
+                self.depth -= 1;
+                result
+            }
+            Some('-') | Some('+') => {
+                // a single sign applies directly to a number, as in `ast.literal_eval`
+                let negative = self.next() == Some('-');
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(c) if c.is_ascii_digit() || c == '.' => {}
+                    _ => return Err(self.error("expected a number after sign")),
+                }
+                match (negative, self.parse_number()?) {
+                    (false, value) => Ok(value),
+                    (true, Literal::Int(v)) => v
+                        .checked_neg()
+                        .map(Literal::Int)
+                        .ok_or_else(|| self.error("integer does not fit in 128 bits")),
+                    (true, Literal::UInt(v)) if v == 1 << 127 => {
+                        Ok(Literal::Int(i128::min_value()))
+                    }
+                    (true, Literal::UInt(_)) => Err(self.error("integer does not fit in 128 bits")),
+                    (true, Literal::Float(v)) => Ok(Literal::Float(-v)),
+                    (true, _) => Err(self.error("expected a number after sign")),
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => self.parse_number(),
+            Some(c) if c.is_alphabetic() || c == '_' || c == '\'' || c == '"' => {
+                if self.string_prefix().is_some() {
+                    self.parse_strings()
+                } else {
+                    self.parse_name()
+                }
+            }
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    /// Parse a list, tuple, dict or set, or a parenthesized value
+    fn parse_container(&mut self) -> Result<Literal> {
+        match self.peek() {
+            Some('[') => {
+                self.next();
+                let (items, _) = self.parse_items(']')?;
+                Ok(Literal::List(items))
+            }
+            Some('(') => {
+                self.next();
+                let (mut items, trailing_comma) = self.parse_items(')')?;
+                if items.len() == 1 && !trailing_comma {
+                    Ok(items.remove(0))
+                } else {
+                    Ok(Literal::Tuple(items))
+                }
+            }
+            Some('{') => {
+                self.next();
+                self.parse_braces()
+            }
+            _ => Err(self.error("unexpected character")),
+        }
+    }
+
+    /// Parse comma separated values up to `close`, noting any trailing comma
+    fn parse_items(&mut self, close: char) -> Result<(Vec<Literal>, bool)> {
+        let mut items = Vec::new();
+        let mut trailing_comma = false;
+        loop {
+            self.skip_whitespace();
+            if self.peek() == Some(close) {
+                self.next();
+                return Ok((items, trailing_comma));
+            }
+            items.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => trailing_comma = true,
+                Some(c) if c == close => {
+                    self.next();
+                    return Ok((items, false));
+                }
+                _ => return Err(self.error(&format!("expected ',' or '{}'", close))),
+            }
+            self.next();
+        }
+    }
+
+    /// Parse a dict or set after its opening brace
+    fn parse_braces(&mut self) -> Result<Literal> {
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.next();
+            return Ok(Literal::Dict(Vec::new()));
+        }
+        let first = self.parse_value()?;
+        self.skip_whitespace();
+        if self.peek() != Some(':') {
+            let mut items = vec![first];
+            match self.peek() {
+                Some(',') | Some('}') => (),
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+            if self.next() == Some(',') {
+                items.extend(self.parse_items('}')?.0);
+            }
+            return Ok(Literal::Set(items));
+        }
+        self.next();
+        let mut entries = vec![(first, self.parse_value()?)];
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') | Some('}') => (),
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+            if self.next() == Some('}') {
+                return Ok(Literal::Dict(entries));
+            }
+            self.skip_whitespace();
+            if self.peek() == Some('}') {
+                self.next();
+                return Ok(Literal::Dict(entries));
+            }
+            let key = self.parse_value()?;
+            self.expect(':')?;
+            entries.push((key, self.parse_value()?));
+        }
+    }
+
+    fn parse_name(&mut self) -> Result<Literal> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_alphanumeric() || c == '_' {
+                self.next();
+            } else {
+                break;
+            }
+        }
+        match &self.input[start..self.pos] {
+            "None" => Ok(Literal::None),
+            "True" => Ok(Literal::Bool(true)),
+            "False" => Ok(Literal::Bool(false)),
+            "set" => {
+                self.expect('(')?;
+                self.expect(')')?;
+                Ok(Literal::Set(Vec::new()))
+            }
+            _ => {
+                self.pos = start;
+                Err(self.error("unsupported name"))
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Literal> {
+        let start = self.pos;
+        let radix = match (self.peek(), self.peek_at(1)) {
+            (Some('0'), Some('x')) | (Some('0'), Some('X')) => 16,
+            (Some('0'), Some('o')) | (Some('0'), Some('O')) => 8,
+            (Some('0'), Some('b')) | (Some('0'), Some('B')) => 2,
+            _ => 10,
+        };
+        let mut float = false;
+        if radix != 10 {
+            self.pos += 2;
+        }
+        while let Some(c) = self.peek() {
+            if c == '_' || c.is_digit(radix) {
+                self.next();
+            } else if radix == 10 && c == '.' {
+                float = true;
+                self.next();
+            } else if radix == 10 && (c == 'e' || c == 'E') {
+                float = true;
+                self.next();
+                if let Some('+') | Some('-') = self.peek() {
+                    self.next();
+                }
+            } else {
+                break;
+            }
+        }
+        if let Some(c) = self.peek() {
+            if c.is_alphanumeric() {
+                return Err(self.error("invalid number"));
+            }
+        }
+        let text = &self.input[start..self.pos];
+        let digits: String = text
+            .chars()
+            .skip(if radix == 10 { 0 } else { 2 })
+            .filter(|&c| c != '_')
+            .collect();
+        if float {
+            return digits
+                .parse()
+                .map(Literal::Float)
+                .map_err(|_| self.error("invalid number"));
+        }
+        if let Ok(v) = i128::from_str_radix(&digits, radix) {
+            Ok(Literal::Int(v))
+        } else if let Ok(v) = u128::from_str_radix(&digits, radix) {
+            Ok(Literal::UInt(v))
+        } else if !digits.is_empty() && digits.chars().all(|c| c.is_digit(radix)) {
+            Err(self.error("integer does not fit in 128 bits"))
+        } else {
+            Err(self.error("invalid number"))
+        }
+    }
+
+    /// The length of a string prefix such as `rb` at the current position, if any
+    fn string_prefix(&self) -> Option<usize> {
+        let rest = &self.input[self.pos..];
+        let len = rest.chars().take_while(|c| "rRbBuU".contains(*c)).count();
+        let prefix = rest[..len].to_ascii_lowercase();
+        let valid = match prefix.as_str() {
+            "" | "r" | "b" | "u" | "rb" | "br" => true,
+            _ => false,
+        };
+        match rest[len..].chars().next() {
+            Some('\'') | Some('"') if valid => Some(len),
+            _ => None,
+        }
+    }
+
+    /// Parse one or more adjacent string or bytes literals, concatenating them
+    fn parse_strings(&mut self) -> Result<Literal> {
+        let (mut text, is_bytes) = self.parse_string()?;
+        loop {
+            let pos = self.pos;
+            self.skip_whitespace();
+            if self.string_prefix().is_none() {
+                self.pos = pos;
+                break;
+            }
+            let (more, more_bytes) = self.parse_string()?;
+            if more_bytes != is_bytes {
+                return Err(self.error("cannot mix bytes and str literals"));
+            }
+            text.extend(more);
+        }
+        if is_bytes {
+            let mut bytes = Vec::with_capacity(text.len());
+            for c in text {
+                if c > 0xff {
+                    return Err(self.error("bytes can only contain ASCII characters"));
+                }
+                bytes.push(c as u8);
+            }
+            Ok(Literal::Bytes(bytes))
+        } else {
+            text.into_iter()
+                .map(std::char::from_u32)
+                .collect::<Option<String>>()
+                .map(Literal::Str)
+                .ok_or_else(|| self.error("invalid character in string"))
+        }
+    }
+
+    /// Parse a single string literal into code points, noting whether it is bytes
+    fn parse_string(&mut self) -> Result<(Vec<u32>, bool)> {
+        let len = self.string_prefix().unwrap_or(0);
+        let prefix = self.input[self.pos..self.pos + len].to_ascii_lowercase();
+        self.pos += len;
+        let is_bytes = prefix.contains('b');
+        let raw = prefix.contains('r');
+        let quote = self.next().unwrap();
+        let triple = self.peek() == Some(quote) && self.peek_at(1) == Some(quote);
+        if triple {
+            self.pos += 2;
+        }
+        let mut text = Vec::new();
+        loop {
+            let c = match self.next() {
+                Some(c) => c,
+                None => return Err(self.error("unterminated string")),
+            };
+            if c == quote {
+                if !triple {
+                    break;
+                }
+                if self.peek() == Some(quote) && self.peek_at(1) == Some(quote) {
+                    self.pos += 2;
+                    break;
+                }
+            } else if c == '\n' && !triple {
+                return Err(self.error("unterminated string"));
+            } else if is_bytes && !c.is_ascii() {
+                return Err(self.error("bytes can only contain ASCII characters"));
+            } else if c == '\\' {
+                let escaped = match self.next() {
+                    Some(escaped) => escaped,
+                    None => return Err(self.error("unterminated string")),
+                };
+                if raw {
+                    text.push('\\' as u32);
+                    text.push(escaped as u32);
+                } else {
+                    self.parse_escape(escaped, is_bytes, &mut text)?;
+                }
+                continue;
+            }
+            text.push(c as u32);
+        }
+        Ok((text, is_bytes))
+    }
+
+    fn parse_escape(&mut self, escaped: char, is_bytes: bool, text: &mut Vec<u32>) -> Result<()> {
+        let value = match escaped {
+            '\n' => return Ok(()),
+            '\\' | '\'' | '"' => escaped as u32,
+            'a' => 0x07,
+            'b' => 0x08,
+            'f' => 0x0c,
+            'n' => 0x0a,
+            'r' => 0x0d,
+            't' => 0x09,
+            'v' => 0x0b,
+            '0'..='7' => {
+                let mut value = escaped.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match self.peek().and_then(|c| c.to_digit(8)) {
+                        Some(digit) => {
+                            value = value * 8 + digit;
+                            self.next();
+                        }
+                        None => break,
+                    }
+                }
+                value
+            }
+            'x' => self.parse_hex(2)?,
+            'u' if !is_bytes => self.parse_hex(4)?,
+            'U' if !is_bytes => self.parse_hex(8)?,
+            'N' if !is_bytes => return Err(self.error("named unicode escapes are not supported")),
+            _ => {
+                // unrecognised escapes are kept as written
+                text.push('\\' as u32);
+                escaped as u32
+            }
+        };
+        text.push(value);
+        Ok(())
+    }
+
+    fn parse_hex(&mut self, len: usize) -> Result<u32> {
+        let start = self.pos;
+        for _ in 0..len {
+            match self.next() {
+                Some(c) if c.is_ascii_hexdigit() => (),
+                _ => return Err(self.error("truncated escape sequence")),
+            }
+        }
+        u32::from_str_radix(&self.input[start..self.pos], 16)
+            .map_err(|_| self.error("invalid escape sequence"))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+impl<'de> IntoDeserializer<'de, Error> for Literal {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl Literal {
+    fn describe(&self) -> &'static str {
+        match self {
+            Literal::None => "None",
+            Literal::Bool(_) => "bool",
+            Literal::Int(_) | Literal::UInt(_) => "int",
+            Literal::Float(_) => "float",
+            Literal::Str(_) => "str",
+            Literal::Bytes(_) => "bytes",
+            Literal::List(_) => "list",
+            Literal::Tuple(_) => "tuple",
+            Literal::Set(_) => "set",
+            Literal::Dict(_) => "dict",
+        }
+    }
+}
+
+fn visit_seq<'de, V>(items: Vec<Literal>, visitor: V) -> Result<V::Value>
+where
+    V: Visitor<'de>,
+{
+    let mut seq = SeqDeserializer::<_, Error>::new(items.into_iter());
+    let value = visitor.visit_seq(&mut seq)?;
+    seq.end()?;
+    Ok(value)
+}
+
+impl<'de> de::Deserializer<'de> for Literal {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Literal::None => visitor.visit_unit(),
+            Literal::Bool(v) => visitor.visit_bool(v),
+            Literal::Int(v)
+                if v >= i128::from(i64::min_value()) && v <= i128::from(u64::max_value()) =>
+            {
+                if v < 0 {
+                    visitor.visit_i64(v as i64)
+                } else {
+                    visitor.visit_u64(v as u64)
+                }
+            }
+            Literal::Int(v) => visitor.visit_i128(v),
+            Literal::UInt(v) => visitor.visit_u128(v),
+            Literal::Float(v) => visitor.visit_f64(v),
+            Literal::Str(v) => visitor.visit_string(v),
+            Literal::Bytes(v) => visitor.visit_byte_buf(v),
+            Literal::List(items) | Literal::Tuple(items) | Literal::Set(items) => {
+                visit_seq(items, visitor)
+            }
+            Literal::Dict(entries) => {
+                let mut map = MapDeserializer::<_, Error>::new(entries.into_iter());
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Literal::None => visitor.visit_none(),
+            value => visitor.visit_some(value),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Literal::Bytes(bytes) => visit_seq(
+                bytes
+                    .into_iter()
+                    .map(|b| Literal::Int(i128::from(b)))
+                    .collect(),
+                visitor,
+            ),
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Literal::Str(variant) => visitor.visit_enum(variant.into_deserializer()),
+            Literal::Dict(mut entries) if entries.len() == 1 => {
+                let (variant, value) = entries.remove(0);
+                visitor.visit_enum(LiteralEnum { variant, value })
+            }
+            value => Err(Error::Message(format!(
+                "expected a variant name or single entry dict, got {}",
+                value.describe()
+            ))),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct LiteralEnum {
+    variant: Literal,
+    value: Literal,
+}
+
+impl<'de> EnumAccess<'de> for LiteralEnum {
+    type Error = Error;
+    type Variant = Literal;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Literal)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        Ok((seed.deserialize(self.variant)?, self.value))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Literal {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        match self {
+            Literal::None => Ok(()),
+            value => Err(Error::Message(format!(
+                "expected None for a unit variant, got {}",
+                value.describe()
+            ))),
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_any(self, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_py;
+    use pyo3::{ObjectProtocol, Python};
+    use serde::Deserialize;
+    use std::collections::{BTreeMap, HashSet};
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    enum E {
+        Unit,
+        Newtype(u32),
+        Tuple(u32, u32),
+        Struct { a: u32 },
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Test {
+        int: i64,
+        big: u128,
+        float: f64,
+        text: String,
+        opt: Option<bool>,
+        seq: Vec<E>,
+        pair: (u8,),
+        map: BTreeMap<String, f32>,
+    }
+
+    fn sample() -> Test {
+        Test {
+            int: -3,
+            big: u128::max_value(),
+            float: 1.0,
+            text: "it's \\ \"é\"\n\u{1}".to_owned(),
+            opt: None,
+            seq: vec![E::Unit, E::Newtype(1), E::Tuple(2, 3), E::Struct { a: 4 }],
+            pair: (5,),
+            map: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_to_string() {
+        assert_eq!(
+            to_string(&(true, None::<u8>, "a'b", 1.5, vec![(1,)])).unwrap(),
+            r#"(True, None, 'a\'b', 1.5, [(1,)])"#
+        );
+        assert_eq!(
+            to_string(&E::Struct { a: 1 }).unwrap(),
+            "{'Struct': {'a': 1}}"
+        );
+        assert_eq!(to_string(&std::f64::INFINITY).unwrap(), "1e999");
+        assert!(to_string(&std::f64::NAN).is_err());
+
+        // the interpreter reads back the same value
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let text = to_string(&sample()).unwrap();
+        let code = format!(
+            "__import__('ast').literal_eval({})",
+            to_string(&text).unwrap()
+        );
+        let input = py.eval(&code, None, None).unwrap();
+        assert_eq!(from_py::<Test>(py, input).unwrap(), sample());
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!(
+            from_str::<Test>(&to_string(&sample()).unwrap()).unwrap(),
+            sample()
+        );
+
+        let result: (Vec<i64>, HashSet<u8>, String, Vec<u8>, f64) = from_str(
+            r#"(
+                [0x1F, 0o17, 0b11, 1_000, -2],  # comment
+                {1, 2,},
+                'a' "b" r'\n' '\x41é',
+                b'\x00\n',
+                -1e999,
+            )"#,
+        )
+        .unwrap();
+        assert_eq!(result.0, vec![31, 15, 3, 1000, -2]);
+        assert_eq!(result.1, vec![1, 2].into_iter().collect());
+        assert_eq!(result.2, "ab\\nAé");
+        assert_eq!(result.3, vec![0, 10]);
+        assert!(result.4.is_infinite() && result.4 < 0.0);
+
+        let result: E = from_str("('Unit')").unwrap();
+        assert_eq!(result, E::Unit);
+        let result: Vec<u32> = from_str("()").unwrap();
+        assert!(result.is_empty());
+
+        let err = from_str::<Vec<u32>>("[1,\n 2 3]").unwrap_err();
+        assert_eq!(err.to_string(), "expected ',' or ']' at line 2 column 4");
+        assert!(from_str::<String>("'a' b'b'").is_err());
+        assert!(from_str::<u32>("len([])").is_err());
+
+        assert_eq!(
+            from_str::<i128>("-170141183460469231731687303715884105728").unwrap(),
+            i128::min_value()
+        );
+        let err = from_str::<i128>("--170141183460469231731687303715884105728").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "expected a number after sign at line 1 column 2"
+        );
+        assert!(from_str::<i64>("+-1").is_err());
+        let err = from_str::<i128>("-340282366920938463463374607431768211455").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "integer does not fit in 128 bits at line 1 column 41"
+        );
+
+        let deep = format!("{}{}", "[".repeat(100), "]".repeat(100));
+        assert!(from_str::<serde_json::Value>(&deep).is_ok());
+        let deep = format!("{}{}", "[".repeat(200), "]".repeat(200));
+        let err = from_str::<serde_json::Value>(&deep).unwrap_err();
+        assert_eq!(err.to_string(), "nesting too deep at line 1 column 129");
+    }
+
+    #[test]
+    fn test_python_repr() {
+        // text produced by the interpreter parses to the same value
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let code = r#"repr({"int": -3, "big": 2**128 - 1, "float": 1.0, "text": "it's \\ \"é\"\n\x01",
+            "opt": None, "seq": ["Unit", {"Newtype": 1}, {"Tuple": (2, 3)}, {"Struct": {"a": 4}}],
+            "pair": (5,), "map": {}})"#;
+        let text: String = py.eval(code, None, None).unwrap().extract().unwrap();
+        assert_eq!(from_str::<Test>(&text).unwrap(), sample());
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+
+use pyo3::PyObject;
+
+/// How enum variants are represented as Python values
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnumRepr {
+    /// `"Unit"`, `{"Newtype": 1}`, `{"Struct": {"a": 1}}`
+    External,
+    /// `{"type": "Struct", "a": 1}`, tuple variants are not supported
+    Internal { tag: &'static str },
+    /// `{"t": "Struct", "c": {"a": 1}}`
+    Adjacent {
+        tag: &'static str,
+        content: &'static str,
+    },
+    /// `"Unit"`, `{"a": 1}`, only unit variants can be deserialized
+    Untagged,
+}
+
+impl Default for EnumRepr {
+    fn default() -> Self {
+        EnumRepr::External
+    }
+}
+
+/// Python type produced for a serialized sequence or tuple
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SeqRepr {
+    List,
+    Tuple,
+}
+
+/// Python value produced for a serialized unit or unit struct
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnitRepr {
+    None,
+    EmptyTuple,
+    EmptyDict,
+}
+
+/// Options controlling the output of `to_py_with`
+#[derive(Debug)]
+pub struct SerializerOptions {
+    pub(crate) classes: HashMap<&'static str, PyObject>,
+    pub(crate) enums: HashMap<&'static str, PyObject>,
+    pub(crate) enum_repr: EnumRepr,
+    pub(crate) seq_repr: SeqRepr,
+    pub(crate) tuple_repr: SeqRepr,
+    pub(crate) unit_repr: UnitRepr,
+    pub(crate) skip_none_fields: bool,
+    pub(crate) sort_keys: bool,
+}
+
+impl SerializerOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the representation used for enum variants
+    pub fn enum_repr(mut self, enum_repr: EnumRepr) -> Self {
+        self.enum_repr = enum_repr;
+        self
+    }
+
+    /// Set the Python type produced for sequences (defaults to `list`)
+    pub fn seq_repr(mut self, seq_repr: SeqRepr) -> Self {
+        self.seq_repr = seq_repr;
+        self
+    }
+
+    /// Set the Python type produced for tuples and tuple structs (defaults to `tuple`)
+    pub fn tuple_repr(mut self, tuple_repr: SeqRepr) -> Self {
+        self.tuple_repr = tuple_repr;
+        self
+    }
+
+    /// Set the Python value produced for unit and unit structs (defaults to `None`)
+    pub fn unit_repr(mut self, unit_repr: UnitRepr) -> Self {
+        self.unit_repr = unit_repr;
+        self
+    }
+
+    /// Omit struct fields with a value of `None`
+    pub fn skip_none_fields(mut self, skip: bool) -> Self {
+        self.skip_none_fields = skip;
+        self
+    }
+
+    /// Sort the keys of serialized maps
+    pub fn sort_keys(mut self, sort: bool) -> Self {
+        self.sort_keys = sort;
+        self
+    }
+
+    /// Produce instances of a namedtuple class for the Rust struct or tuple struct `name`
+    pub fn namedtuple(self, name: &'static str, class: PyObject) -> Self {
+        self.class(name, class)
+    }
+
+    /// Call a Python class for the struct `name` (or `"Enum::Variant"` for struct variants),
+    /// passing fields as keyword arguments, or positionally for tuple structs
+    pub fn class(mut self, name: &'static str, class: PyObject) -> Self {
+        self.classes.insert(name, class);
+        self
+    }
+
+    /// Serialize unit variants of the Rust enum `name` as members of a Python `enum.Enum`
+    pub fn python_enum(mut self, name: &'static str, class: PyObject) -> Self {
+        self.enums.insert(name, class);
+        self
+    }
+}
+
+impl Default for SerializerOptions {
+    fn default() -> Self {
+        Self {
+            classes: HashMap::new(),
+            enums: HashMap::new(),
+            enum_repr: EnumRepr::default(),
+            seq_repr: SeqRepr::List,
+            tuple_repr: SeqRepr::Tuple,
+            unit_repr: UnitRepr::None,
+            skip_none_fields: false,
+            sort_keys: false,
+        }
+    }
+}
+
+/// How strictly Python values must match the Rust types being deserialized
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Coercion {
+    /// Accept any value `FromPyObject` will extract
+    Default,
+    /// Reject `bool` as an integer, `str` and `bytes` as sequences and `int` as a float
+    Strict,
+    /// Also accept numeric strings such as `"42"` or `"1.5"` as numbers
+    Lenient,
+}
+
+/// Options controlling the input accepted by `from_py_with`
+#[derive(Clone, Copy, Debug)]
+pub struct DeserializerOptions {
+    pub(crate) enum_repr: EnumRepr,
+    pub(crate) coercion: Coercion,
+    pub(crate) collect_errors: bool,
+    pub(crate) big_int_strings: bool,
+    pub(crate) prefer_attributes: bool,
+    pub(crate) match_enum_value: bool,
+}
+
+impl DeserializerOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the representation expected for enum variants
+    pub fn enum_repr(mut self, enum_repr: EnumRepr) -> Self {
+        self.enum_repr = enum_repr;
+        self
+    }
+
+    /// Set how Python values are coerced into Rust types
+    pub fn coercion(mut self, coercion: Coercion) -> Self {
+        self.coercion = coercion;
+        self
+    }
+
+    /// Continue past type mismatches, returning every error as `Error::Multiple`
+    pub fn collect_errors(mut self, collect: bool) -> Self {
+        self.collect_errors = collect;
+        self
+    }
+
+    /// Bring ints too large for 128 bits into Rust as decimal strings instead of failing
+    pub fn big_int_strings(mut self, enable: bool) -> Self {
+        self.big_int_strings = enable;
+        self
+    }
+
+    /// Read struct fields from object attributes even when the object is also a mapping
+    pub fn prefer_attributes(mut self, prefer: bool) -> Self {
+        self.prefer_attributes = prefer;
+        self
+    }
+
+    /// Match Python `enum.Enum` members to variants by `.value` instead of `.name`, for members
+    /// with a str value
+    pub fn match_enum_value(mut self, by_value: bool) -> Self {
+        self.match_enum_value = by_value;
+        self
+    }
+}
+
+impl Default for DeserializerOptions {
+    fn default() -> Self {
+        Self {
+            enum_repr: EnumRepr::default(),
+            coercion: Coercion::Default,
+            collect_errors: false,
+            big_int_strings: false,
+            prefer_attributes: false,
+            match_enum_value: false,
+        }
+    }
+}
@@ -0,0 +1,1089 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use serde::de::DeserializeOwned;
+use serde::{ser, Serialize};
+
+use super::error::{Error, Result};
+use super::literal::{Literal, MAX_DEPTH};
+
+/// Most values built from a pickle, counting each copy of a shared object
+const MAX_VALUES: usize = 1 << 20;
+
+const PROTO: u8 = 0x80;
+const STOP: u8 = b'.';
+const MARK: u8 = b'(';
+const NONE: u8 = b'N';
+const NEWTRUE: u8 = 0x88;
+const NEWFALSE: u8 = 0x89;
+const BININT: u8 = b'J';
+const BININT1: u8 = b'K';
+const BININT2: u8 = b'M';
+const LONG1: u8 = 0x8a;
+const BINFLOAT: u8 = b'G';
+const BINUNICODE: u8 = b'X';
+const BINBYTES: u8 = b'B';
+const SHORT_BINBYTES: u8 = b'C';
+const EMPTY_LIST: u8 = b']';
+const APPENDS: u8 = b'e';
+const TUPLE: u8 = b't';
+const EMPTY_DICT: u8 = b'}';
+const SETITEM: u8 = b's';
+const SETITEMS: u8 = b'u';
+
+/// Encode a value as a protocol 3 pickle, with the same Python shapes as `to_py`
+pub fn to_pickle<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: ?Sized + Serialize,
+{
+    let mut serializer = PickleSerializer {
+        output: vec![PROTO, 3],
+    };
+    value.serialize(&mut serializer)?;
+    serializer.output.push(STOP);
+    Ok(serializer.output)
+}
+
+/// Decode a pickle, refusing any opcode which could import or call Python code
+pub fn from_pickle<T>(input: &[u8]) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    let mut unpickler = Unpickler {
+        input,
+        pos: 0,
+        nodes: Vec::new(),
+        stack: Vec::new(),
+        marks: Vec::new(),
+        memo: HashMap::new(),
+    };
+    let value = unpickler.load()?;
+    if unpickler.pos < input.len() {
+        return Err(unpickler.error("unexpected data after STOP"));
+    }
+    T::deserialize(value)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+struct PickleSerializer {
+    output: Vec<u8>,
+}
+
+impl PickleSerializer {
+    fn write_len(&mut self, len: usize) -> Result<()> {
+        let len = u32::try_from(len).map_err(|_| {
+            Error::Serialization(format!("length {} is too large for a pickle", len))
+        })?;
+        self.output.extend_from_slice(&len.to_le_bytes());
+        Ok(())
+    }
+
+    fn write_int(&mut self, v: i128) {
+        if v >= 0 && v <= 0xff {
+            self.output.extend_from_slice(&[BININT1, v as u8]);
+        } else if v >= 0 && v <= 0xffff {
+            self.output.push(BININT2);
+            self.output.extend_from_slice(&(v as u16).to_le_bytes());
+        } else if let Ok(v) = i32::try_from(v) {
+            self.output.push(BININT);
+            self.output.extend_from_slice(&v.to_le_bytes());
+        } else {
+            self.write_long(v.to_le_bytes().to_vec());
+        }
+    }
+
+    /// Write a little-endian two's complement integer, dropping redundant sign bytes
+    fn write_long(&mut self, mut bytes: Vec<u8>) {
+        while bytes.len() > 1 {
+            let last = bytes[bytes.len() - 1];
+            let sign = bytes[bytes.len() - 2] & 0x80;
+            if (last == 0 && sign == 0) || (last == 0xff && sign != 0) {
+                bytes.pop();
+            } else {
+                break;
+            }
+        }
+        self.output.extend_from_slice(&[LONG1, bytes.len() as u8]);
+        self.output.extend(bytes);
+    }
+
+    fn write_str(&mut self, v: &str) -> Result<()> {
+        self.output.push(BINUNICODE);
+        self.write_len(v.len())?;
+        self.output.extend_from_slice(v.as_bytes());
+        Ok(())
+    }
+
+    /// Open a compound value, writing `close` when done
+    fn compound(&mut self, open: &[u8], close: &'static [u8]) -> Compound {
+        self.output.extend_from_slice(open);
+        self.output.push(MARK);
+        Compound { ser: self, close }
+    }
+
+    /// Open a dict holding a single variant, as produced by `to_py` for external tagging
+    fn open_variant(&mut self, variant: &str) -> Result<()> {
+        self.output.push(EMPTY_DICT);
+        self.write_str(variant)
+    }
+}
+
+struct Compound<'a> {
+    ser: &'a mut PickleSerializer,
+    close: &'static [u8],
+}
+
+impl<'a> Compound<'a> {
+    fn element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(&mut *self.ser)
+    }
+
+    fn field<T>(&mut self, key: &str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.ser.write_str(key)?;
+        value.serialize(&mut *self.ser)
+    }
+
+    fn finish(self) -> Result<()> {
+        self.ser.output.extend_from_slice(self.close);
+        Ok(())
+    }
+}
+
+impl<'a> ser::Serializer for &'a mut PickleSerializer {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Compound<'a>;
+    type SerializeTuple = Compound<'a>;
+    type SerializeTupleStruct = Compound<'a>;
+    type SerializeTupleVariant = Compound<'a>;
+    type SerializeMap = Compound<'a>;
+    type SerializeStruct = Compound<'a>;
+    type SerializeStructVariant = Compound<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.output.push(if v { NEWTRUE } else { NEWFALSE });
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.serialize_i128(i128::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.serialize_i128(i128::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.serialize_i128(i128::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        self.serialize_i128(i128::from(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        self.write_int(v);
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.serialize_i128(i128::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.serialize_i128(i128::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.serialize_i128(i128::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        self.serialize_i128(i128::from(v))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        match i128::try_from(v) {
+            Ok(v) => self.write_int(v),
+            Err(_) => {
+                // a zero byte keeps the sign bit clear
+                let mut bytes = v.to_le_bytes().to_vec();
+                bytes.push(0);
+                self.write_long(bytes);
+            }
+        }
+        Ok(())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        self.serialize_f64(f64::from(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        self.output.push(BINFLOAT);
+        self.output.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.write_str(v.encode_utf8(&mut [0u8; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.write_str(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        if v.len() <= 0xff {
+            self.output
+                .extend_from_slice(&[SHORT_BINBYTES, v.len() as u8]);
+        } else {
+            self.output.push(BINBYTES);
+            self.write_len(v.len())?;
+        }
+        self.output.extend_from_slice(v);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        self.output.push(NONE);
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.write_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.open_variant(variant)?;
+        value.serialize(&mut *self)?;
+        self.output.push(SETITEM);
+        Ok(())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'a>> {
+        Ok(self.compound(&[EMPTY_LIST], &[APPENDS]))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Compound<'a>> {
+        Ok(self.compound(&[], &[TUPLE]))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Compound<'a>> {
+        Ok(self.compound(&[], &[TUPLE]))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>> {
+        self.open_variant(variant)?;
+        Ok(self.compound(&[], &[TUPLE, SETITEM]))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Compound<'a>> {
+        Ok(self.compound(&[EMPTY_DICT], &[SETITEMS]))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Compound<'a>> {
+        Ok(self.compound(&[EMPTY_DICT], &[SETITEMS]))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>> {
+        self.open_variant(variant)?;
+        Ok(self.compound(&[EMPTY_DICT], &[SETITEMS, SETITEM]))
+    }
+}
+
+impl<'a> ser::SerializeSeq for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeTuple for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeTupleStruct for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeTupleVariant for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeMap for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(key)
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeStruct for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeStructVariant for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// An object created while unpickling, containers refer to others by index
+enum Node {
+    Value(Literal),
+    List(Vec<usize>),
+    Tuple(Vec<usize>),
+    Set(Vec<usize>),
+    Dict(Vec<(usize, usize)>),
+}
+
+struct Unpickler<'a> {
+    input: &'a [u8],
+    pos: usize,
+    nodes: Vec<Node>,
+    stack: Vec<usize>,
+    marks: Vec<usize>,
+    memo: HashMap<usize, usize>,
+}
+
+impl<'a> Unpickler<'a> {
+    fn error(&self, msg: &str) -> Error {
+        Error::Message(format!("invalid pickle: {} at offset {}", msg, self.pos))
+    }
+
+    fn read(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.input.len() - self.pos < len {
+            return Err(self.error("unexpected end of data"));
+        }
+        let data = &self.input[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(data)
+    }
+
+    fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read(1)?[0])
+    }
+
+    fn read_u32(&mut self) -> Result<usize> {
+        let mut buf = [0u8; 4];
+        buf.copy_from_slice(self.read(4)?);
+        Ok(u32::from_le_bytes(buf) as usize)
+    }
+
+    fn read_u64(&mut self) -> Result<usize> {
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(self.read(8)?);
+        usize::try_from(u64::from_le_bytes(buf)).map_err(|_| self.error("length out of range"))
+    }
+
+    /// Read the text argument of a protocol 0 opcode, up to the next newline
+    fn read_line(&mut self) -> Result<&'a str> {
+        let len = self.input[self.pos..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| self.error("unterminated line"))?;
+        let line = self.read(len + 1)?;
+        std::str::from_utf8(&line[..len]).map_err(|_| self.error("invalid text argument"))
+    }
+
+    fn read_utf8(&mut self, len: usize) -> Result<Literal> {
+        let data = self.read(len)?;
+        String::from_utf8(data.to_vec())
+            .map(Literal::Str)
+            .map_err(|_| self.error("invalid UTF-8 in string"))
+    }
+
+    fn push(&mut self, node: Node) {
+        self.nodes.push(node);
+        self.stack.push(self.nodes.len() - 1);
+    }
+
+    fn pop(&mut self) -> Result<usize> {
+        match self.stack.pop() {
+            Some(idx)
+                if self
+                    .marks
+                    .last()
+                    .map_or(true, |&mark| mark <= self.stack.len()) =>
+            {
+                Ok(idx)
+            }
+            _ => Err(self.error("stack underflow")),
+        }
+    }
+
+    fn top(&self) -> Result<usize> {
+        match self.stack.last() {
+            Some(&idx)
+                if self
+                    .marks
+                    .last()
+                    .map_or(true, |&mark| mark < self.stack.len()) =>
+            {
+                Ok(idx)
+            }
+            _ => Err(self.error("stack underflow")),
+        }
+    }
+
+    /// Remove the objects pushed since the last `MARK`
+    fn pop_mark(&mut self) -> Result<Vec<usize>> {
+        let mark = self.marks.pop().ok_or_else(|| self.error("missing MARK"))?;
+        Ok(self.stack.split_off(mark))
+    }
+
+    fn pop_pairs(&mut self) -> Result<Vec<(usize, usize)>> {
+        let items = self.pop_mark()?;
+        if items.len() % 2 != 0 {
+            return Err(self.error("odd number of items for dict"));
+        }
+        Ok(items.chunks(2).map(|pair| (pair[0], pair[1])).collect())
+    }
+
+    fn load(&mut self) -> Result<Literal> {
+        loop {
+            let op = self.read_u8()?;
+            match op {
+                PROTO => {
+                    if self.read_u8()? > 5 {
+                        return Err(self.error("unsupported protocol"));
+                    }
+                }
+                // FRAME
+                0x95 => {
+                    self.read(8)?;
+                }
+                STOP => {
+                    let root = self.pop()?;
+                    return self.build(root, &mut Vec::new(), &mut 0);
+                }
+                MARK => self.marks.push(self.stack.len()),
+                // POP
+                b'0' => {
+                    self.pop()?;
+                }
+                // POP_MARK
+                b'1' => {
+                    self.pop_mark()?;
+                }
+                // DUP
+                b'2' => {
+                    let top = self.top()?;
+                    self.stack.push(top);
+                }
+                NONE => self.push(Node::Value(Literal::None)),
+                NEWTRUE => self.push(Node::Value(Literal::Bool(true))),
+                NEWFALSE => self.push(Node::Value(Literal::Bool(false))),
+                // INT
+                b'I' => {
+                    let value = match self.read_line()? {
+                        "00" => Literal::Bool(false),
+                        "01" => Literal::Bool(true),
+                        line => self.parse_int(line)?,
+                    };
+                    self.push(Node::Value(value));
+                }
+                BININT => {
+                    let mut buf = [0u8; 4];
+                    buf.copy_from_slice(self.read(4)?);
+                    let value = i32::from_le_bytes(buf);
+                    self.push(Node::Value(Literal::Int(i128::from(value))));
+                }
+                BININT1 => {
+                    let value = self.read_u8()?;
+                    self.push(Node::Value(Literal::Int(i128::from(value))));
+                }
+                BININT2 => {
+                    let mut buf = [0u8; 2];
+                    buf.copy_from_slice(self.read(2)?);
+                    let value = u16::from_le_bytes(buf);
+                    self.push(Node::Value(Literal::Int(i128::from(value))));
+                }
+                // LONG
+                b'L' => {
+                    let line = self.read_line()?;
+                    let value = self.parse_int(line.trim_end_matches('L'))?;
+                    self.push(Node::Value(value));
+                }
+                LONG1 => {
+                    let len = self.read_u8()? as usize;
+                    let value = self.decode_long(len)?;
+                    self.push(Node::Value(value));
+                }
+                // LONG4
+                0x8b => {
+                    let len = self.read_u32()?;
+                    let value = self.decode_long(len)?;
+                    self.push(Node::Value(value));
+                }
+                // FLOAT
+                b'F' => {
+                    let line = self.read_line()?;
+                    let value = line.parse().map_err(|_| self.error("invalid float"))?;
+                    self.push(Node::Value(Literal::Float(value)));
+                }
+                BINFLOAT => {
+                    let mut buf = [0u8; 8];
+                    buf.copy_from_slice(self.read(8)?);
+                    self.push(Node::Value(Literal::Float(f64::from_be_bytes(buf))));
+                }
+                // UNICODE
+                b'V' => {
+                    let len = self.input[self.pos..]
+                        .iter()
+                        .position(|&b| b == b'\n')
+                        .ok_or_else(|| self.error("unterminated line"))?;
+                    let line = self.read(len + 1)?;
+                    let value = self.decode_raw_unicode(&line[..len])?;
+                    self.push(Node::Value(Literal::Str(value)));
+                }
+                // BINSTRING, SHORT_BINSTRING, BINUNICODE, SHORT_BINUNICODE, BINUNICODE8
+                b'T' | b'U' | BINUNICODE | 0x8c | 0x8d => {
+                    let len = match op {
+                        b'U' | 0x8c => self.read_u8()? as usize,
+                        0x8d => self.read_u64()?,
+                        _ => self.read_u32()?,
+                    };
+                    let value = self.read_utf8(len)?;
+                    self.push(Node::Value(value));
+                }
+                // BINBYTES, SHORT_BINBYTES, BINBYTES8, BYTEARRAY8
+                BINBYTES | SHORT_BINBYTES | 0x8e | 0x96 => {
+                    let len = match op {
+                        SHORT_BINBYTES => self.read_u8()? as usize,
+                        BINBYTES => self.read_u32()?,
+                        _ => self.read_u64()?,
+                    };
+                    let value = self.read(len)?.to_vec();
+                    self.push(Node::Value(Literal::Bytes(value)));
+                }
+                EMPTY_LIST => self.push(Node::List(Vec::new())),
+                // LIST
+                b'l' => {
+                    let items = self.pop_mark()?;
+                    self.push(Node::List(items));
+                }
+                // APPEND
+                b'a' => {
+                    let item = self.pop()?;
+                    self.extend_list(vec![item])?;
+                }
+                APPENDS => {
+                    let items = self.pop_mark()?;
+                    self.extend_list(items)?;
+                }
+                // EMPTY_TUPLE
+                b')' => self.push(Node::Tuple(Vec::new())),
+                TUPLE => {
+                    let items = self.pop_mark()?;
+                    self.push(Node::Tuple(items));
+                }
+                // TUPLE1, TUPLE2, TUPLE3
+                0x85..=0x87 => {
+                    let len = (op - 0x84) as usize;
+                    let mut items = Vec::with_capacity(len);
+                    for _ in 0..len {
+                        items.push(self.pop()?);
+                    }
+                    items.reverse();
+                    self.push(Node::Tuple(items));
+                }
+                EMPTY_DICT => self.push(Node::Dict(Vec::new())),
+                // DICT
+                b'd' => {
+                    let entries = self.pop_pairs()?;
+                    self.push(Node::Dict(entries));
+                }
+                SETITEM => {
+                    let value = self.pop()?;
+                    let key = self.pop()?;
+                    self.extend_dict(vec![(key, value)])?;
+                }
+                SETITEMS => {
+                    let entries = self.pop_pairs()?;
+                    self.extend_dict(entries)?;
+                }
+                // EMPTY_SET
+                0x8f => self.push(Node::Set(Vec::new())),
+                // ADDITEMS
+                0x90 => {
+                    let items = self.pop_mark()?;
+                    let top = self.top()?;
+                    match &mut self.nodes[top] {
+                        Node::Set(set) => set.extend(items),
+                        _ => return Err(self.error("ADDITEMS to a non-set")),
+                    }
+                }
+                // FROZENSET
+                0x91 => {
+                    let items = self.pop_mark()?;
+                    self.push(Node::Set(items));
+                }
+                // PUT, BINPUT, LONG_BINPUT, MEMOIZE
+                b'p' | b'q' | b'r' | 0x94 => {
+                    let key = match op {
+                        b'p' => self
+                            .read_line()?
+                            .parse::<usize>()
+                            .map_err(|_| self.error("invalid PUT"))?,
+                        b'q' => self.read_u8()? as usize,
+                        b'r' => self.read_u32()?,
+                        _ => self.memo.len(),
+                    };
+                    let top = self.top()?;
+                    self.memo.insert(key, top);
+                }
+                // GET, BINGET, LONG_BINGET
+                b'g' | b'h' | b'j' => {
+                    let key = match op {
+                        b'g' => self
+                            .read_line()?
+                            .parse::<usize>()
+                            .map_err(|_| self.error("invalid GET"))?,
+                        b'h' => self.read_u8()? as usize,
+                        _ => self.read_u32()?,
+                    };
+                    let idx = *self
+                        .memo
+                        .get(&key)
+                        .ok_or_else(|| self.error("memo key not found"))?;
+                    self.stack.push(idx);
+                }
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.error(&format!("unsupported opcode 0x{:02x}", op)));
+                }
+            }
+        }
+    }
+
+    fn extend_list(&mut self, items: Vec<usize>) -> Result<()> {
+        let top = self.top()?;
+        match &mut self.nodes[top] {
+            Node::List(list) => {
+                list.extend(items);
+                Ok(())
+            }
+            _ => Err(self.error("APPEND to a non-list")),
+        }
+    }
+
+    fn extend_dict(&mut self, entries: Vec<(usize, usize)>) -> Result<()> {
+        let top = self.top()?;
+        match &mut self.nodes[top] {
+            Node::Dict(dict) => {
+                dict.extend(entries);
+                Ok(())
+            }
+            _ => Err(self.error("SETITEM on a non-dict")),
+        }
+    }
+
+    fn parse_int(&self, text: &str) -> Result<Literal> {
+        if let Ok(v) = text.parse() {
+            Ok(Literal::Int(v))
+        } else if let Ok(v) = text.parse() {
+            Ok(Literal::UInt(v))
+        } else {
+            Err(self.error("invalid or out of range integer"))
+        }
+    }
+
+    /// Decode a little-endian two's complement integer of up to 128 bits
+    fn decode_long(&mut self, len: usize) -> Result<Literal> {
+        let bytes = self.read(len)?;
+        let negative = bytes.last().map_or(false, |b| b & 0x80 != 0);
+        let fill = if negative { 0xff } else { 0 };
+        let (low, high) = bytes.split_at(len.min(16));
+        if high.iter().any(|&b| b != fill) || (negative && len > 16 && low[15] & 0x80 == 0) {
+            return Err(self.error("integer does not fit in 128 bits"));
+        }
+        let mut buf = [fill; 16];
+        buf[..low.len()].copy_from_slice(low);
+        if negative {
+            Ok(Literal::Int(i128::from_le_bytes(buf)))
+        } else {
+            let value = u128::from_le_bytes(buf);
+            Ok(i128::try_from(value).map_or(Literal::UInt(value), Literal::Int))
+        }
+    }
+
+    /// Decode the `raw-unicode-escape` text written by protocol 0
+    fn decode_raw_unicode(&self, data: &[u8]) -> Result<String> {
+        let mut output = String::with_capacity(data.len());
+        let mut pos = 0;
+        while pos < data.len() {
+            let digits = match &data[pos..] {
+                [b'\\', b'u', ..] => 4,
+                [b'\\', b'U', ..] => 8,
+                _ => 0,
+            };
+            if digits == 0 {
+                // other bytes are latin-1
+                output.push(data[pos] as char);
+                pos += 1;
+                continue;
+            }
+            let hex = data
+                .get(pos + 2..pos + 2 + digits)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .and_then(std::char::from_u32)
+                .ok_or_else(|| self.error("invalid escape in string"))?;
+            output.push(hex);
+            pos += 2 + digits;
+        }
+        Ok(output)
+    }
+
+    /// Convert an object to a value tree, copying any shared objects
+    fn build(&self, idx: usize, active: &mut Vec<usize>, built: &mut usize) -> Result<Literal> {
+        if active.contains(&idx) {
+            return Err(self.error("recursive objects are not supported"));
+        }
+        if active.len() == MAX_DEPTH {
+            return Err(self.error("nesting too deep"));
+        }
+        *built += 1;
+        if *built > MAX_VALUES {
+            return Err(self.error("too many values"));
+        }
+        active.push(idx);
+        let build_all = |items: &[usize], active: &mut Vec<usize>, built: &mut usize| {
+            items
+                .iter()
+                .map(|&item| self.build(item, active, built))
+                .collect::<Result<Vec<_>>>()
+        };
+        let value = match &self.nodes[idx] {
+            Node::Value(value) => value.clone(),
+            Node::List(items) => Literal::List(build_all(items, active, built)?),
+            Node::Tuple(items) => Literal::Tuple(build_all(items, active, built)?),
+            Node::Set(items) => Literal::Set(build_all(items, active, built)?),
+            Node::Dict(entries) => Literal::Dict(
+                entries
+                    .iter()
+                    .map(|&(key, value)| -> Result<(Literal, Literal)> {
+                        Ok((
+                            self.build(key, active, built)?,
+                            self.build(value, active, built)?,
+                        ))
+                    })
+                    .collect::<Result<_>>()?,
+            ),
+        };
+        active.pop();
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::to_py;
+    use pyo3::py_run;
+    use pyo3::types::{PyBytes, PyDict};
+    use pyo3::{ObjectProtocol, Python};
+    use serde::{Deserialize, Serializer};
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    enum E {
+        Unit,
+        Newtype(i64),
+        Tuple(u8, u8),
+        Struct { a: u32 },
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Test {
+        small: i8,
+        wide: i64,
+        neg: i128,
+        big: u128,
+        float: f64,
+        text: String,
+        opt: Option<bool>,
+        seq: Vec<E>,
+        pair: (u8, String),
+        map: BTreeMap<String, Vec<u16>>,
+    }
+
+    fn sample() -> Test {
+        let mut map = BTreeMap::new();
+        map.insert("a".to_owned(), vec![1, 300, 65535]);
+        map.insert("é".to_owned(), vec![]);
+        Test {
+            small: -1,
+            wide: 1 << 40,
+            neg: i128::min_value(),
+            big: u128::max_value(),
+            float: -2.5,
+            text: "x\ny\\é\u{1F600}".to_owned(),
+            opt: None,
+            seq: vec![
+                E::Unit,
+                E::Newtype(-70000),
+                E::Tuple(1, 2),
+                E::Struct { a: 3 },
+            ],
+            pair: (255, "b".to_owned()),
+            map,
+        }
+    }
+
+    struct Bytes<'a>(&'a [u8]);
+
+    impl<'a> Serialize for Bytes<'a> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(self.0)
+        }
+    }
+
+    #[test]
+    fn test_to_pickle() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        let data = PyBytes::new(py, &to_pickle(&sample()).unwrap());
+        let expected = to_py(py, &sample()).unwrap();
+        py_run!(
+            py,
+            data expected,
+            "import pickle; assert pickle.loads(data) == expected"
+        );
+
+        let long = vec![7u8; 300];
+        let data = PyBytes::new(py, &to_pickle(&(Bytes(b"a\x00"), Bytes(&long))).unwrap());
+        py_run!(
+            py,
+            data,
+            "import pickle; assert pickle.loads(data) == (b'a\\x00', b'\\x07' * 300)"
+        );
+    }
+
+    #[test]
+    fn test_from_pickle() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let locals = PyDict::new(py);
+        locals
+            .set_item("obj", to_py(py, &sample()).unwrap())
+            .unwrap();
+        for protocol in 0..=4 {
+            let code = format!("__import__('pickle').dumps(obj, protocol={})", protocol);
+            let data: Vec<u8> = py
+                .eval(&code, None, Some(locals))
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(from_pickle::<Test>(&data).unwrap(), sample());
+        }
+
+        assert_eq!(
+            from_pickle::<Test>(&to_pickle(&sample()).unwrap()).unwrap(),
+            sample()
+        );
+
+        let data: Vec<u8> = py
+            .eval(
+                "__import__('pickle').dumps(({1, 2}, frozenset(), b'xy', [[3]] * 2), protocol=4)",
+                None,
+                None,
+            )
+            .unwrap()
+            .extract()
+            .unwrap();
+        let result: (Vec<u8>, Vec<u8>, Vec<u8>, Vec<Vec<u8>>) = from_pickle(&data).unwrap();
+        assert_eq!(
+            result,
+            (vec![1, 2], vec![], b"xy".to_vec(), vec![vec![3], vec![3]])
+        );
+    }
+
+    #[test]
+    fn test_unsafe_pickle() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        for code in &[
+            "__import__('pickle').dumps(__import__('datetime').date(2020, 1, 1))",
+            "__import__('pickle').dumps(print)",
+            "(lambda l: (l.append(l), __import__('pickle').dumps(l))[1])([])",
+        ] {
+            let data: Vec<u8> = py.eval(code, None, None).unwrap().extract().unwrap();
+            assert!(from_pickle::<Vec<u8>>(&data).is_err());
+        }
+        let err = from_pickle::<u8>(b"cos\nsystem\n.").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid pickle: unsupported opcode 0x63 at offset 0"
+        );
+        assert!(from_pickle::<u8>(b"\x80\x03K\x01").is_err());
+    }
+
+    #[test]
+    fn test_pickle_limits() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let dumps = |code: &str| -> Vec<u8> {
+            let code = format!("__import__('pickle').dumps({}, protocol=4)", code);
+            py.eval(&code, None, None).unwrap().extract().unwrap()
+        };
+
+        // each level refers to the one below twice, doubling the size of the value tree
+        let data = dumps("__import__('functools').reduce(lambda x, _: [x, x], range(40), [0])");
+        let err = from_pickle::<serde_json::Value>(&data).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("invalid pickle: too many values"));
+
+        let data = dumps("__import__('functools').reduce(lambda x, _: [x], range(200), [])");
+        let err = from_pickle::<serde_json::Value>(&data).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("invalid pickle: nesting too deep"));
+        let data = dumps("__import__('functools').reduce(lambda x, _: [x], range(100), [])");
+        assert!(from_pickle::<serde_json::Value>(&data).is_ok());
+    }
+}
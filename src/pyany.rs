@@ -0,0 +1,150 @@
+use std::cell::RefCell;
+use std::fmt;
+
+use pyo3::{AsPyRef, PyAny, PyObject, Python};
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::ser::{self, Serialize, Serializer};
+
+/// Newtype struct name recognised by this crate's `Serializer` and `Deserializer`
+pub(crate) const TOKEN: &str = "$serde_pyo3::private::PyAnyField";
+
+thread_local! {
+    /// The object being handed between a `PyAnyField` and this crate's serializers
+    static HANDOFF: RefCell<Option<PyObject>> = RefCell::new(None);
+}
+
+/// Store the object for a `PyAnyField` to pick up
+pub(crate) fn put(obj: PyObject) {
+    HANDOFF.with(|slot| slot.replace(Some(obj)));
+}
+
+/// Take the object stored by the other side of the handshake
+pub(crate) fn take() -> Option<PyObject> {
+    HANDOFF.with(|slot| slot.borrow_mut().take())
+}
+
+/// A Python object carried through serialization and deserialization without conversion
+#[derive(Debug)]
+pub struct PyAnyField(pub PyObject);
+
+impl PyAnyField {
+    pub fn new(obj: PyObject) -> Self {
+        PyAnyField(obj)
+    }
+
+    pub fn as_ref<'p>(&'p self, py: Python<'p>) -> &'p PyAny {
+        self.0.as_ref(py)
+    }
+
+    pub fn into_inner(self) -> PyObject {
+        self.0
+    }
+}
+
+impl From<PyObject> for PyAnyField {
+    fn from(obj: PyObject) -> Self {
+        PyAnyField(obj)
+    }
+}
+
+impl Serialize for PyAnyField {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let gil = Python::acquire_gil();
+        put(self.0.clone_ref(gil.python()));
+        let result = serializer.serialize_newtype_struct(TOKEN, &Opaque);
+        // clear the object if another serializer ignored it
+        take();
+        result
+    }
+}
+
+/// Stands in for the object, failing in serializers other than this crate's
+struct Opaque;
+
+impl Serialize for Opaque {
+    fn serialize<S>(&self, _serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Err(missing())
+    }
+}
+
+struct PyAnyFieldVisitor;
+
+impl<'de> Visitor<'de> for PyAnyFieldVisitor {
+    type Value = PyAnyField;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a Python object")
+    }
+
+    fn visit_unit<E>(self) -> std::result::Result<PyAnyField, E>
+    where
+        E: de::Error,
+    {
+        take()
+            .map(PyAnyField)
+            .ok_or_else(|| E::custom("PyAnyField can only be deserialized by serde_pyo3"))
+    }
+}
+
+impl<'de> Deserialize<'de> for PyAnyField {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_newtype_struct(TOKEN, PyAnyFieldVisitor)
+    }
+}
+
+/// Raise an error when the serializer could not find the object for a `PyAnyField`
+pub(crate) fn missing<E: ser::Error>() -> E {
+    E::custom("PyAnyField can only be serialized by serde_pyo3")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_py, to_py};
+    use pyo3::py_run;
+    use pyo3::types::PyDict;
+    use pyo3::AsPyPointer;
+
+    #[test]
+    fn test_roundtrip() {
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Job {
+            name: String,
+            callback: PyAnyField,
+        }
+
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let locals = PyDict::new(py);
+        py.run(
+            "callback = lambda: 1\njob = {'name': 'a', 'callback': callback}",
+            None,
+            Some(locals),
+        )
+        .unwrap();
+        let callback = locals.get_item("callback").unwrap();
+
+        let job: Job = from_py(py, locals.get_item("job").unwrap()).unwrap();
+        assert_eq!(job.name, "a");
+        assert_eq!(job.callback.as_ref(py).as_ptr(), callback.as_ptr());
+
+        let output = to_py(py, &job).unwrap();
+        py_run!(
+            py,
+            output callback,
+            "assert output == {'name': 'a', 'callback': callback}"
+        );
+
+        assert!(serde_json::to_string(&job).is_err());
+        assert!(serde_json::from_str::<PyAnyField>("null").is_err());
+    }
+}
@@ -1,20 +1,27 @@
 use pyo3::types::{PyDict, PyList, PyTuple};
-use pyo3::{PyObject, Python, ToPyObject};
+use pyo3::{AsPyRef, ObjectProtocol, PyAny, PyObject, PyTryFrom, Python, ToPyObject};
 use serde::{ser, Serialize};
 
+use super::array;
 use super::error::{Error, Result};
+use super::options::{EnumRepr, SeqRepr, SerializerOptions, UnitRepr};
+use super::pyany;
 
 pub struct Serializer<'a> {
     pub py: Python<'a>,
+    options: &'a SerializerOptions,
 }
 pub struct PyDictSerializer<'a> {
     root: &'a Serializer<'a>,
+    class: Option<&'a PyObject>,
     dict: &'a PyDict,
     key: Option<PyObject>,
 }
 pub struct PyDictVariantSerializer<'a> {
     root: &'a Serializer<'a>,
-    variant: PyObject,
+    class: Option<&'a PyObject>,
+    name: &'static str,
+    variant: &'static str,
     dict: &'a PyDict,
 }
 pub struct PyListSerializer<'a> {
@@ -23,11 +30,13 @@ pub struct PyListSerializer<'a> {
 }
 pub struct PyTupleSerializer<'a> {
     root: &'a Serializer<'a>,
+    class: Option<&'a PyObject>,
     stack: Vec<PyObject>,
 }
 pub struct PyTupleVariantSerializer<'a> {
     root: &'a Serializer<'a>,
-    variant: PyObject,
+    name: &'static str,
+    variant: &'static str,
     stack: Vec<PyObject>,
 }
 
@@ -35,10 +44,82 @@ pub fn to_py<'a, T>(py: Python<'a>, value: &T) -> Result<PyObject>
 where
     T: Serialize,
 {
-    let serializer = Serializer { py };
+    to_py_with(py, value, &SerializerOptions::default())
+}
+
+pub fn to_py_with<'a, T>(py: Python<'a>, value: &T, options: &SerializerOptions) -> Result<PyObject>
+where
+    T: Serialize,
+{
+    let serializer = Serializer::new(py, options);
     Ok(value.serialize(&serializer)?)
 }
 
+impl<'a> Serializer<'a> {
+    pub fn new(py: Python<'a>, options: &'a SerializerOptions) -> Self {
+        Serializer { py, options }
+    }
+
+    /// Build a tuple or list from serialized tuple elements
+    fn tuple_object(&self, stack: Vec<PyObject>) -> PyObject {
+        match self.options.tuple_repr {
+            SeqRepr::Tuple => PyTuple::new(self.py, stack).to_object(self.py),
+            SeqRepr::List => PyList::new(self.py, stack).to_object(self.py),
+        }
+    }
+
+    /// Combine a variant name with its content (`None` for unit variants)
+    fn wrap_variant(
+        &self,
+        name: &'static str,
+        variant: &'static str,
+        value: Option<PyObject>,
+    ) -> Result<PyObject> {
+        let py = self.py;
+        match self.options.enum_repr {
+            EnumRepr::External => match value {
+                Some(value) => {
+                    let dict = PyDict::new(py);
+                    dict.set_item(variant, value)?;
+                    Ok(dict.to_object(py))
+                }
+                None => Ok(variant.to_object(py)),
+            },
+            EnumRepr::Internal { tag } => {
+                let dict = PyDict::new(py);
+                dict.set_item(tag, variant)?;
+                if let Some(value) = value {
+                    let value = value.as_ref(py);
+                    if let Ok(fields) = <PyDict as PyTryFrom>::try_from(value) {
+                        for (key, val) in fields.iter() {
+                            dict.set_item(key, val)?;
+                        }
+                    } else if !value.is_none() {
+                        return Err(Error::Serialization(format!(
+                            "cannot serialize internally tagged variant {}::{} containing a non-dict value",
+                            name, variant
+                        )));
+                    }
+                }
+                Ok(dict.to_object(py))
+            }
+            EnumRepr::Adjacent { tag, content } => {
+                let dict = PyDict::new(py);
+                dict.set_item(tag, variant)?;
+                if let Some(value) = value {
+                    dict.set_item(content, value)?;
+                }
+                Ok(dict.to_object(py))
+            }
+            // unit variants keep their name so they can be deserialized again
+            EnumRepr::Untagged => match value {
+                Some(value) => Ok(value),
+                None => Ok(variant.to_object(py)),
+            },
+        }
+    }
+}
+
 impl<'a> ser::Serializer for &'a Serializer<'a> {
     type Ok = PyObject;
 
@@ -76,6 +157,10 @@ impl<'a> ser::Serializer for &'a Serializer<'a> {
         Ok(v.to_object(self.py))
     }
 
+    fn serialize_i128(self, v: i128) -> Result<PyObject> {
+        Ok(v.to_object(self.py))
+    }
+
     fn serialize_u8(self, v: u8) -> Result<PyObject> {
         self.serialize_u64(u64::from(v))
     }
@@ -92,6 +177,10 @@ impl<'a> ser::Serializer for &'a Serializer<'a> {
         Ok(v.to_object(self.py))
     }
 
+    fn serialize_u128(self, v: u128) -> Result<PyObject> {
+        Ok(v.to_object(self.py))
+    }
+
     fn serialize_f32(self, v: f32) -> Result<PyObject> {
         self.serialize_f64(f64::from(v))
     }
@@ -125,34 +214,51 @@ impl<'a> ser::Serializer for &'a Serializer<'a> {
     }
 
     fn serialize_unit(self) -> Result<PyObject> {
-        // Ok(PyTuple::empty(self.py).to_object(self.py))
-        Ok(self.py.None())
+        Ok(match self.options.unit_repr {
+            UnitRepr::None => self.py.None(),
+            UnitRepr::EmptyTuple => PyTuple::empty(self.py).to_object(self.py),
+            UnitRepr::EmptyDict => PyDict::new(self.py).to_object(self.py),
+        })
     }
 
     fn serialize_unit_struct(self, _name: &'static str) -> Result<PyObject> {
-        // FIXME how should this be represented?
         self.serialize_unit()
     }
 
     fn serialize_unit_variant(
         self,
-        _name: &'static str,
+        name: &'static str,
         _variant_index: u32,
         variant: &'static str,
     ) -> Result<PyObject> {
-        self.serialize_str(variant)
+        if let Some(class) = self.options.enums.get(name) {
+            return Ok(class.as_ref(self.py).get_item(variant)?.to_object(self.py));
+        }
+        self.wrap_variant(name, variant, None)
     }
 
-    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<PyObject>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<PyObject>
     where
         T: ?Sized + Serialize,
     {
+        if name == pyany::TOKEN {
+            return pyany::take().ok_or_else(pyany::missing);
+        }
+        if let Some(typecode) = array::typecode(name) {
+            array::request_raw();
+            value.serialize(self)?;
+            let bytes = array::take_raw().ok_or_else(|| {
+                Error::Serialization(format!("expected the raw contents of {}", name))
+            })?;
+            let obj = self.py.import("array")?.call1("array", (typecode, bytes))?;
+            return Ok(obj.to_object(self.py));
+        }
         value.serialize(self)
     }
 
     fn serialize_newtype_variant<T>(
         self,
-        _name: &'static str,
+        name: &'static str,
         _variant_index: u32,
         variant: &'static str,
         value: &T,
@@ -160,11 +266,8 @@ impl<'a> ser::Serializer for &'a Serializer<'a> {
     where
         T: ?Sized + Serialize,
     {
-        let dict = PyDict::new(self.py);
-        let key = variant.serialize(&*self)?;
         let value = value.serialize(&*self)?;
-        dict.set_item(key, value)?;
-        Ok(dict.to_object(self.py))
+        self.wrap_variant(name, variant, Some(value))
     }
 
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
@@ -177,31 +280,39 @@ impl<'a> ser::Serializer for &'a Serializer<'a> {
     fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
         Ok(PyTupleSerializer {
             root: self,
+            class: None,
             stack: Vec::with_capacity(len),
         })
     }
 
     fn serialize_tuple_struct(
         self,
-        _name: &'static str,
+        name: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleStruct> {
         Ok(PyTupleSerializer {
             root: self,
+            class: self.options.classes.get(name),
             stack: Vec::with_capacity(len),
         })
     }
 
     fn serialize_tuple_variant(
         self,
-        _name: &'static str,
+        name: &'static str,
         _variant_index: u32,
         variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        let variant = variant.serialize(&*self)?;
+        if let EnumRepr::Internal { .. } = self.options.enum_repr {
+            return Err(Error::Serialization(format!(
+                "cannot serialize internally tagged tuple variant {}::{}",
+                name, variant
+            )));
+        }
         Ok(PyTupleVariantSerializer {
             root: self,
+            name,
             variant,
             stack: Vec::with_capacity(len),
         })
@@ -210,27 +321,38 @@ impl<'a> ser::Serializer for &'a Serializer<'a> {
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
         Ok(PyDictSerializer {
             root: self,
+            class: None,
             dict: PyDict::new(self.py),
             key: None,
         })
     }
 
-    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
-        self.serialize_map(Some(len))
+    fn serialize_struct(self, name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Ok(PyDictSerializer {
+            root: self,
+            class: self.options.classes.get(name),
+            dict: PyDict::new(self.py),
+            key: None,
+        })
     }
 
     fn serialize_struct_variant(
         self,
-        _name: &'static str,
+        name: &'static str,
         _variant_index: u32,
         variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        let variant = variant.serialize(&*self)?;
+        let class = self
+            .options
+            .classes
+            .get(format!("{}::{}", name, variant).as_str());
         Ok(PyDictVariantSerializer {
             root: self,
-            dict: PyDict::new(self.py),
+            class,
+            name,
             variant,
+            dict: PyDict::new(self.py),
         })
     }
 }
@@ -248,7 +370,14 @@ impl<'a> ser::SerializeSeq for PyListSerializer<'a> {
     }
 
     fn end(self) -> Result<PyObject> {
-        Ok(self.list.to_object(self.root.py))
+        let py = self.root.py;
+        match self.root.options.seq_repr {
+            SeqRepr::List => Ok(self.list.to_object(py)),
+            SeqRepr::Tuple => {
+                let items: Vec<&PyAny> = self.list.iter().collect();
+                Ok(PyTuple::new(py, items).to_object(py))
+            }
+        }
     }
 }
 
@@ -265,7 +394,7 @@ impl<'a> ser::SerializeTuple for PyTupleSerializer<'a> {
     }
 
     fn end(self) -> Result<PyObject> {
-        Ok(PyTuple::new(self.root.py, self.stack).to_object(self.root.py))
+        Ok(self.root.tuple_object(self.stack))
     }
 }
 
@@ -282,7 +411,11 @@ impl<'a> ser::SerializeTupleStruct for PyTupleSerializer<'a> {
     }
 
     fn end(self) -> Result<PyObject> {
-        Ok(PyTuple::new(self.root.py, self.stack).to_object(self.root.py))
+        let py = self.root.py;
+        match self.class {
+            Some(class) => Ok(class.call1(py, PyTuple::new(py, self.stack))?),
+            None => Ok(self.root.tuple_object(self.stack)),
+        }
     }
 }
 
@@ -299,10 +432,8 @@ impl<'a> ser::SerializeTupleVariant for PyTupleVariantSerializer<'a> {
     }
 
     fn end(self) -> Result<PyObject> {
-        let dict = PyDict::new(self.root.py);
-        let tuple = PyTuple::new(self.root.py, self.stack).to_object(self.root.py);
-        dict.set_item(self.variant, tuple)?;
-        Ok(dict.to_object(self.root.py))
+        let tuple = self.root.tuple_object(self.stack);
+        self.root.wrap_variant(self.name, self.variant, Some(tuple))
     }
 }
 
@@ -328,7 +459,20 @@ impl<'a> ser::SerializeMap for PyDictSerializer<'a> {
     }
 
     fn end(self) -> Result<PyObject> {
-        Ok(self.dict.to_object(self.root.py))
+        let py = self.root.py;
+        if self.root.options.sort_keys {
+            let keys = self.dict.keys();
+            keys.call_method0("sort")?;
+            let sorted = PyDict::new(py);
+            for key in keys.iter() {
+                if let Some(value) = self.dict.get_item(key) {
+                    sorted.set_item(key, value)?;
+                }
+            }
+            Ok(sorted.to_object(py))
+        } else {
+            Ok(self.dict.to_object(py))
+        }
     }
 }
 
@@ -340,14 +484,20 @@ impl<'a> ser::SerializeStruct for PyDictSerializer<'a> {
     where
         T: ?Sized + Serialize,
     {
-        let key = key.serialize(self.root)?;
         let value = value.serialize(self.root)?;
+        if self.root.options.skip_none_fields && value.is_none() {
+            return Ok(());
+        }
         self.dict.set_item(key, value)?;
         Ok(())
     }
 
     fn end(self) -> Result<PyObject> {
-        Ok(self.dict.to_object(self.root.py))
+        let py = self.root.py;
+        match self.class {
+            Some(class) => Ok(class.call(py, (), Some(self.dict))?),
+            None => Ok(self.dict.to_object(py)),
+        }
     }
 }
 
@@ -359,17 +509,24 @@ impl<'a> ser::SerializeStructVariant for PyDictVariantSerializer<'a> {
     where
         T: ?Sized + Serialize,
     {
-        let key = key.serialize(self.root)?;
         let value = value.serialize(self.root)?;
+        if self.root.options.skip_none_fields && value.is_none() {
+            return Ok(());
+        }
         self.dict.set_item(key, value)?;
         Ok(())
     }
 
     fn end(self) -> Result<PyObject> {
-        let result = PyDict::new(self.root.py);
-        let dict = self.dict.to_object(self.root.py);
-        result.set_item(self.variant, dict)?;
-        Ok(result.to_object(self.root.py))
+        let py = self.root.py;
+        match self.class {
+            // the registered class identifies the variant, so it is not tagged
+            Some(class) => Ok(class.call(py, (), Some(self.dict))?),
+            None => {
+                let dict = self.dict.to_object(py);
+                self.root.wrap_variant(self.name, self.variant, Some(dict))
+            }
+        }
     }
 }
 
@@ -403,6 +560,7 @@ mod test {
     }
 
     #[test]
+    #[allow(clippy::string_lit_as_bytes)]
     fn test_bytes() {
         let gil = Python::acquire_gil();
         let py = gil.python();
@@ -414,6 +572,134 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_int128() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let max = to_py(py, &u128::max_value()).unwrap();
+        let min = to_py(py, &i128::min_value()).unwrap();
+        py_run!(
+            py,
+            max min,
+            "assert max == 2**128 - 1; assert min == -2**127"
+        );
+    }
+
+    #[test]
+    fn test_namedtuple() {
+        #[derive(Serialize)]
+        struct Point {
+            x: i32,
+            y: i32,
+        }
+
+        #[derive(Serialize)]
+        struct Pair(u32, u32);
+
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let collections = py.import("collections").unwrap();
+        let point_class = collections
+            .call1("namedtuple", ("Point", vec!["x", "y"]))
+            .unwrap();
+        let pair_class = collections
+            .call1("namedtuple", ("Pair", vec!["a", "b"]))
+            .unwrap();
+        let options = SerializerOptions::new()
+            .namedtuple("Point", point_class.to_object(py))
+            .namedtuple("Pair", pair_class.to_object(py));
+
+        let point = to_py_with(py, &Point { x: 1, y: 2 }, &options).unwrap();
+        let pair = to_py_with(py, &Pair(3, 4), &options).unwrap();
+        py_run!(
+            py,
+            point pair point_class pair_class,
+            r#"
+            assert isinstance(point, point_class) and point == (1, 2) and point.y == 2
+            assert isinstance(pair, pair_class) and pair.b == 4
+        "#
+        );
+    }
+
+    #[test]
+    fn test_class() {
+        #[derive(Serialize)]
+        struct User {
+            name: &'static str,
+            admin: bool,
+        }
+
+        #[derive(Serialize)]
+        enum Shape {
+            Circle { radius: u32 },
+        }
+
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let locals = PyDict::new(py);
+        py.run(
+            r#"
+from dataclasses import dataclass
+
+@dataclass
+class User:
+    name: str
+    admin: bool = False
+
+class Circle:
+    def __init__(self, radius):
+        self.radius = radius
+"#,
+            None,
+            Some(locals),
+        )
+        .unwrap();
+        let user_class = locals.get_item("User").unwrap();
+        let circle_class = locals.get_item("Circle").unwrap();
+        let options = SerializerOptions::new()
+            .class("User", user_class.to_object(py))
+            .class("Shape::Circle", circle_class.to_object(py));
+
+        let user = User {
+            name: "ann",
+            admin: true,
+        };
+        let user = to_py_with(py, &user, &options).unwrap();
+        let circle = to_py_with(py, &Shape::Circle { radius: 3 }, &options).unwrap();
+        py_run!(
+            py,
+            user user_class circle circle_class,
+            r#"
+            assert user == user_class("ann", True)
+            assert isinstance(circle, circle_class) and circle.radius == 3
+        "#
+        );
+    }
+
+    #[test]
+    fn test_python_enum() {
+        #[derive(Serialize)]
+        enum Color {
+            Red,
+            Green,
+        }
+
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let enum_class = py
+            .eval("__import__('enum').Enum('Color', 'Red Green')", None, None)
+            .unwrap();
+        let options = SerializerOptions::new().python_enum("Color", enum_class.to_object(py));
+
+        let red = to_py_with(py, &Color::Red, &options).unwrap();
+        let green = to_py_with(py, &vec![Color::Green], &options).unwrap();
+        py_run!(
+            py,
+            red green enum_class,
+            "assert red is enum_class.Red; assert green == [enum_class.Green]"
+        );
+    }
+
     #[test]
     fn test_enum() {
         #[derive(Serialize)]
@@ -439,4 +725,93 @@ mod test {
         let s = to_py(py, &E::Struct { a: 1 }).unwrap();
         py_run!(py, s, "assert s == {'Struct': {'a': 1}}");
     }
+
+    #[test]
+    fn test_enum_repr() {
+        #[derive(Serialize)]
+        enum E {
+            Unit,
+            Newtype(u32),
+            Tuple(u32, u32),
+            Struct { a: u32 },
+        }
+
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        let internal = SerializerOptions::new().enum_repr(EnumRepr::Internal { tag: "type" });
+        let u = to_py_with(py, &E::Unit, &internal).unwrap();
+        py_run!(py, u, "assert u == {'type': 'Unit'}");
+        let s = to_py_with(py, &E::Struct { a: 1 }, &internal).unwrap();
+        py_run!(py, s, "assert s == {'type': 'Struct', 'a': 1}");
+        assert!(to_py_with(py, &E::Newtype(1), &internal).is_err());
+        assert!(to_py_with(py, &E::Tuple(1, 2), &internal).is_err());
+
+        let adjacent = SerializerOptions::new().enum_repr(EnumRepr::Adjacent {
+            tag: "t",
+            content: "c",
+        });
+        let u = to_py_with(py, &E::Unit, &adjacent).unwrap();
+        py_run!(py, u, "assert u == {'t': 'Unit'}");
+        let t = to_py_with(py, &E::Tuple(1, 2), &adjacent).unwrap();
+        py_run!(py, t, "assert t == {'t': 'Tuple', 'c': (1, 2)}");
+
+        let untagged = SerializerOptions::new().enum_repr(EnumRepr::Untagged);
+        let u = to_py_with(py, &E::Unit, &untagged).unwrap();
+        py_run!(py, u, "assert u == 'Unit'");
+        let n = to_py_with(py, &E::Newtype(1), &untagged).unwrap();
+        py_run!(py, n, "assert n == 1");
+        let s = to_py_with(py, &E::Struct { a: 1 }, &untagged).unwrap();
+        py_run!(py, s, "assert s == {'a': 1}");
+    }
+
+    #[test]
+    fn test_options() {
+        use std::collections::HashMap;
+
+        #[derive(Serialize)]
+        struct Unit;
+
+        #[derive(Serialize)]
+        struct Test {
+            seq: Vec<u32>,
+            tuple: (u32, u32),
+            unit: Unit,
+            opt: Option<u32>,
+            map: HashMap<&'static str, u32>,
+        }
+
+        let test = Test {
+            seq: vec![1, 2],
+            tuple: (3, 4),
+            unit: Unit,
+            opt: None,
+            map: vec![("c", 3), ("a", 1), ("b", 2)].into_iter().collect(),
+        };
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        let obj = to_py(py, &test).unwrap();
+        py_run!(
+            py,
+            obj,
+            "assert obj == {'seq': [1, 2], 'tuple': (3, 4), 'unit': None, 'opt': None, 'map': {'a': 1, 'b': 2, 'c': 3}}"
+        );
+
+        let options = SerializerOptions::new()
+            .seq_repr(SeqRepr::Tuple)
+            .tuple_repr(SeqRepr::List)
+            .unit_repr(UnitRepr::EmptyDict)
+            .skip_none_fields(true)
+            .sort_keys(true);
+        let obj = to_py_with(py, &test, &options).unwrap();
+        py_run!(
+            py,
+            obj,
+            r#"
+            assert obj == {'seq': (1, 2), 'tuple': [3, 4], 'unit': {}, 'map': {'a': 1, 'b': 2, 'c': 3}}
+            assert list(obj['map']) == ['a', 'b', 'c']
+        "#
+        );
+    }
 }
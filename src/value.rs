@@ -0,0 +1,395 @@
+use std::cell::Cell;
+use std::fmt;
+
+use pyo3::types::{
+    PyBool, PyByteArray, PyBytes, PyDict, PyFloat, PyFrozenSet, PyIterator, PyList, PyLong, PySet,
+    PyString, PyTuple,
+};
+use pyo3::{
+    AsPyPointer, ObjectProtocol, PyAny, PyErr, PyNativeType, PyObject, PyTryFrom, PyTypeInfo,
+    Python, ToPyObject,
+};
+use serde::de::{
+    self, DeserializeSeed, Deserializer, EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor,
+};
+use serde::ser::{self, Serialize, SerializeMap, SerializeSeq, Serializer};
+
+use super::error::{Error, Found};
+
+/// Serialize a Python object into any serde format
+pub struct PySerialize<'a> {
+    obj: &'a PyAny,
+    sort_keys: bool,
+    parent: Option<&'a PySerialize<'a>>,
+}
+
+fn py_err<E: ser::Error>(err: PyErr) -> E {
+    E::custom(Error::from(err))
+}
+
+impl<'a> PySerialize<'a> {
+    pub fn new(obj: &'a PyAny) -> Self {
+        PySerialize {
+            obj,
+            sort_keys: false,
+            parent: None,
+        }
+    }
+
+    /// Sort the keys of dicts
+    pub fn sort_keys(mut self, sort: bool) -> Self {
+        self.sort_keys = sort;
+        self
+    }
+
+    /// Serialize an item of this container
+    fn child(&'a self, obj: &'a PyAny) -> Self {
+        PySerialize {
+            obj,
+            sort_keys: self.sort_keys,
+            parent: Some(self),
+        }
+    }
+
+    /// Fail if this container is already being serialized further up the tree
+    fn check_cycle<E: ser::Error>(&self) -> Result<(), E> {
+        let mut parent = self.parent;
+        while let Some(container) = parent {
+            if container.obj.as_ptr() == self.obj.as_ptr() {
+                return Err(E::custom(Error::Serialization(
+                    "circular reference detected".to_owned(),
+                )));
+            }
+            parent = container.parent;
+        }
+        Ok(())
+    }
+
+    fn serialize_int<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let obj = self.obj;
+        if let Ok(val) = obj.extract::<i64>() {
+            serializer.serialize_i64(val)
+        } else if let Ok(val) = obj.extract::<u64>() {
+            serializer.serialize_u64(val)
+        } else if let Ok(val) = obj.extract::<i128>() {
+            serializer.serialize_i128(val)
+        } else if let Ok(val) = obj.extract::<u128>() {
+            serializer.serialize_u128(val)
+        } else {
+            let value = obj.str().map_err(py_err)?.to_string_lossy().into_owned();
+            // only negative values can't fit in an unsigned integer
+            let signed = value.starts_with('-');
+            Err(ser::Error::custom(Error::IntegerOverflow {
+                bits: 128,
+                signed,
+                value,
+            }))
+        }
+    }
+
+    fn serialize_iter<S>(&self, serializer: S, len: usize) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let iter = PyIterator::from_object(self.obj.py(), self.obj)
+            .map_err(|err| py_err(PyErr::from(err)))?;
+        let mut seq = serializer.serialize_seq(Some(len))?;
+        for item in iter {
+            seq.serialize_element(&self.child(item.map_err(py_err)?))?;
+        }
+        seq.end()
+    }
+}
+
+impl<'a> Serialize for PySerialize<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let obj = self.obj;
+        if obj.is_none() {
+            serializer.serialize_unit()
+        } else if let Ok(val) = <PyBool as PyTryFrom>::try_from(obj) {
+            serializer.serialize_bool(val.is_true())
+        } else if <PyLong as PyTypeInfo>::is_instance(obj) {
+            self.serialize_int(serializer)
+        } else if let Ok(val) = <PyFloat as PyTryFrom>::try_from(obj) {
+            serializer.serialize_f64(val.value())
+        } else if let Ok(val) = <PyString as PyTryFrom>::try_from(obj) {
+            serializer.serialize_str(&val.to_string().map_err(py_err)?)
+        } else if let Ok(val) = <PyBytes as PyTryFrom>::try_from(obj) {
+            serializer.serialize_bytes(val.as_bytes())
+        } else if let Ok(val) = <PyByteArray as PyTryFrom>::try_from(obj) {
+            serializer.serialize_bytes(&val.to_vec())
+        } else if let Ok(dict) = <PyDict as PyTryFrom>::try_from(obj) {
+            self.check_cycle()?;
+            let mut map = serializer.serialize_map(Some(dict.len()))?;
+            if self.sort_keys {
+                let keys = dict.keys();
+                keys.call_method0("sort").map_err(py_err)?;
+                for key in keys.iter() {
+                    if let Some(value) = dict.get_item(key) {
+                        map.serialize_entry(&self.child(key), &self.child(value))?;
+                    }
+                }
+            } else {
+                for (key, value) in dict.iter() {
+                    map.serialize_entry(&self.child(key), &self.child(value))?;
+                }
+            }
+            map.end()
+        } else if let Ok(list) = <PyList as PyTryFrom>::try_from(obj) {
+            self.check_cycle()?;
+            self.serialize_iter(serializer, list.len())
+        } else if let Ok(tuple) = <PyTuple as PyTryFrom>::try_from(obj) {
+            self.check_cycle()?;
+            self.serialize_iter(serializer, tuple.len())
+        } else if <PySet as PyTypeInfo>::is_instance(obj)
+            || <PyFrozenSet as PyTypeInfo>::is_instance(obj)
+        {
+            self.check_cycle()?;
+            self.serialize_iter(serializer, obj.len().map_err(py_err)?)
+        } else {
+            Err(ser::Error::custom(Error::Unsupported(Found::new(obj))))
+        }
+    }
+}
+
+/// Build a Python object from the data in any serde `Deserializer`
+pub fn py_from_deserializer<'de, D>(py: Python, deserializer: D) -> Result<PyObject, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    PyObjectSeed(py).deserialize(deserializer)
+}
+
+/// Deserializes untyped data into Python objects
+#[derive(Clone, Copy)]
+pub struct PyObjectSeed<'p>(pub Python<'p>);
+
+fn de_err<E: de::Error>(err: PyErr) -> E {
+    E::custom(Error::from(err))
+}
+
+impl<'de, 'p> DeserializeSeed<'de> for PyObjectSeed<'p> {
+    type Value = PyObject;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<PyObject, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, 'p> Visitor<'de> for PyObjectSeed<'p> {
+    type Value = PyObject;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<PyObject, E> {
+        Ok(v.to_object(self.0))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<PyObject, E> {
+        Ok(v.to_object(self.0))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<PyObject, E> {
+        Ok(v.to_object(self.0))
+    }
+
+    fn visit_i128<E>(self, v: i128) -> Result<PyObject, E> {
+        Ok(v.to_object(self.0))
+    }
+
+    fn visit_u128<E>(self, v: u128) -> Result<PyObject, E> {
+        Ok(v.to_object(self.0))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<PyObject, E> {
+        Ok(v.to_object(self.0))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<PyObject, E> {
+        Ok(v.to_object(self.0))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<PyObject, E> {
+        Ok(PyBytes::new(self.0, v).to_object(self.0))
+    }
+
+    fn visit_none<E>(self) -> Result<PyObject, E> {
+        Ok(self.0.None())
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<PyObject, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        self.deserialize(deserializer)
+    }
+
+    fn visit_unit<E>(self) -> Result<PyObject, E> {
+        Ok(self.0.None())
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<PyObject, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        self.deserialize(deserializer)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<PyObject, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let list = PyList::empty(self.0);
+        while let Some(item) = seq.next_element_seed(self)? {
+            list.append(item).map_err(de_err)?;
+        }
+        Ok(list.to_object(self.0))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<PyObject, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let dict = PyDict::new(self.0);
+        while let Some((key, value)) = map.next_entry_seed(self, self)? {
+            dict.set_item(key, value).map_err(de_err)?;
+        }
+        Ok(dict.to_object(self.0))
+    }
+
+    fn visit_enum<A>(self, data: A) -> Result<PyObject, A::Error>
+    where
+        A: EnumAccess<'de>,
+    {
+        let (variant, access) = data.variant::<String>()?;
+        let payload = Cell::new(false);
+        match access.newtype_variant_seed(VariantSeed(self, &payload)) {
+            Ok(value) => {
+                let dict = PyDict::new(self.0);
+                dict.set_item(variant, value).map_err(de_err)?;
+                Ok(dict.to_object(self.0))
+            }
+            // the access rejected a newtype variant without reading a payload, so this is a unit
+            // variant, which becomes the bare name as in `serialize_unit_variant`
+            Err(_) if !payload.get() => Ok(variant.to_object(self.0)),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// Deserializes the payload of an enum variant, recording whether the format provided one
+struct VariantSeed<'a, 'p>(PyObjectSeed<'p>, &'a Cell<bool>);
+
+impl<'de, 'a, 'p> DeserializeSeed<'de> for VariantSeed<'a, 'p> {
+    type Value = PyObject;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<PyObject, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        self.1.set(true);
+        self.0.deserialize(deserializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::py_run;
+    use serde_json::json;
+
+    #[test]
+    fn test_serialize() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let obj = py
+            .eval(
+                r#"{"a": [1, -2, 2**63, 1.5, None], "b": (True, "x"), "c": {3}, "d": b"hi"}"#,
+                None,
+                None,
+            )
+            .unwrap();
+        let value = serde_json::to_value(PySerialize::new(obj)).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "a": [1, -2, 9_223_372_036_854_775_808_u64, 1.5, null],
+                "b": [true, "x"],
+                "c": [3],
+                "d": [104, 105],
+            })
+        );
+
+        let obj = py.eval("object()", None, None).unwrap();
+        let err = serde_json::to_string(&PySerialize::new(obj)).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("unsupported input value, got 'object'"));
+
+        let obj = py
+            .eval("(lambda l: l.append([l]) or l)([])", None, None)
+            .unwrap();
+        let err = serde_json::to_string(&PySerialize::new(obj)).unwrap_err();
+        assert_eq!(err.to_string(), "circular reference detected");
+
+        let obj = py.eval("2 ** 128", None, None).unwrap();
+        let err = serde_json::to_string(&PySerialize::new(obj)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "integer out of range for u128, got 340282366920938463463374607431768211456"
+        );
+        let obj = py.eval("-2 ** 127 - 1", None, None).unwrap();
+        let err = serde_json::to_string(&PySerialize::new(obj)).unwrap_err();
+        assert!(err.to_string().starts_with("integer out of range for i128"));
+        let obj = py.eval("(lambda x: [x, (x, x)])([1])", None, None).unwrap();
+        let value = serde_json::to_value(PySerialize::new(obj)).unwrap();
+        assert_eq!(value, json!([[1], [[1], [1]]]));
+    }
+
+    #[test]
+    fn test_from_deserializer() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let mut deserializer = serde_json::Deserializer::from_str(
+            r#"{"a": [1, -2, 18446744073709551615, 1.5, null], "b": {"c": true, "d": "x"}}"#,
+        );
+        let obj = py_from_deserializer(py, &mut deserializer).unwrap();
+        py_run!(
+            py,
+            obj,
+            r#"
+            assert obj == {"a": [1, -2, 2**64 - 1, 1.5, None], "b": {"c": True, "d": "x"}}
+        "#
+        );
+
+        let mut deserializer = serde_json::Deserializer::from_str("[1,");
+        assert!(py_from_deserializer(py, &mut deserializer).is_err());
+
+        let variants = &["Unit", "Newtype"];
+        let mut deserializer = serde_json::Deserializer::from_str(r#""Unit""#);
+        let obj = deserializer
+            .deserialize_enum("E", variants, PyObjectSeed(py))
+            .unwrap();
+        py_run!(py, obj, r#"assert obj == "Unit""#);
+        let mut deserializer = serde_json::Deserializer::from_str(r#"{"Newtype": [1]}"#);
+        let obj = deserializer
+            .deserialize_enum("E", variants, PyObjectSeed(py))
+            .unwrap();
+        py_run!(py, obj, r#"assert obj == {"Newtype": [1]}"#);
+        let mut deserializer = serde_json::Deserializer::from_str(r#"{"Newtype": [1,}"#);
+        assert!(deserializer
+            .deserialize_enum("E", variants, PyObjectSeed(py))
+            .is_err());
+    }
+}